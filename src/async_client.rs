@@ -0,0 +1,392 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use crate::ipc::{client::Target, platform::unix_connection::open_pipe, utils};
+use crate::rpc::{activity::Activity, command::Command};
+use crate::{ClientKind, SocketPolicy};
+
+/// Events emitted by the background reader of an [`AsyncRichClient`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Ready,
+    Update(String),
+    Disconnected,
+    Error(String),
+}
+
+/// A [`Stream`] of [`Event`]s, obtained through
+/// [`AsyncRichClient::events`].
+pub struct Events {
+    receiver: mpsc::UnboundedReceiver<Event>,
+}
+
+impl Stream for Events {
+    type Item = Event;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Non-blocking counterpart of [`crate::RichClient`], built on tokio.
+///
+/// Sockets are discovered the same way, including abstract sockets and
+/// other client kinds, and checked against the same [`SocketPolicy`].
+///
+/// Must be used from within a tokio runtime.
+pub struct AsyncRichClient<'a> {
+    pub client_id: u64,
+    pub pid: u32,
+    last_activity: Option<Activity<'a>>,
+    writer: Option<OwnedWriteHalf>,
+    /// Where the background reader sends events, once
+    /// [`AsyncRichClient::events`] was called.
+    events: Arc<Mutex<Option<mpsc::UnboundedSender<Event>>>>,
+    handle: Option<JoinHandle<()>>,
+    target: Option<Target>,
+    socket_policy: SocketPolicy,
+}
+
+impl<'a> AsyncRichClient<'a> {
+    pub fn new(client_id: u64) -> Self {
+        Self {
+            client_id,
+            pid: std::process::id(),
+            last_activity: None,
+            writer: None,
+            events: Arc::default(),
+            handle: None,
+            target: None,
+            socket_policy: SocketPolicy::default(),
+        }
+    }
+
+    /// Which discovered sockets to trust. Defaults to
+    /// [`SocketPolicy::Any`].
+    pub fn socket_policy(mut self, policy: SocketPolicy) -> Self {
+        self.socket_policy = policy;
+        self
+    }
+
+    /// The kind of client the last connection was made to, or `None` if
+    /// the client never connected.
+    pub fn client_kind(&self) -> Option<ClientKind> {
+        self.target.as_ref().map(|target| target.kind)
+    }
+
+    /// The sockets [`SocketPolicy`] skipped before the last connection was
+    /// made, each with the reason. Empty if none were skipped or the client
+    /// never connected.
+    pub fn skipped_sockets(&self) -> &[String] {
+        self.target
+            .as_ref()
+            .map_or(&[], |target| target.skipped.as_slice())
+    }
+
+    /// Connects to Discord and resolves once the handshake has been
+    /// acknowledged. Reconnects if the previous connection was lost.
+    pub async fn connect(&mut self) -> io::Result<()> {
+        self.drop_lost();
+        if self.writer.is_some() {
+            return Ok(());
+        }
+
+        let stream = self.open().await?;
+        self.handshake(stream).await
+    }
+
+    /// Subscribes to the events of this and later connections, replacing
+    /// the stream returned by a previous call, which then ends. Events are
+    /// only kept while a stream is subscribed.
+    pub fn events(&mut self) -> Events {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        *self.events.lock().unwrap() = Some(sender);
+        Events { receiver }
+    }
+
+    pub async fn clear(&mut self) -> io::Result<()> {
+        self.write(
+            1,
+            Command::SetActivity {
+                pid: self.pid,
+                activity: None,
+            }
+            .to_json("-")
+            .unwrap()
+            .as_bytes(),
+        )
+        .await?;
+        self.last_activity = None;
+
+        Ok(())
+    }
+
+    pub async fn update(&mut self, activity: Activity<'a>) -> io::Result<()> {
+        if self.last_activity.as_ref() != Some(&activity) {
+            self.write(
+                1,
+                Command::SetActivity {
+                    pid: self.pid,
+                    activity: Some(&activity),
+                }
+                .to_json("-")
+                .unwrap()
+                .as_bytes(),
+            )
+            .await?;
+            self.last_activity = Some(activity);
+        }
+
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) -> io::Result<()> {
+        let Some(mut writer) = self.writer.take() else {
+            return Ok(());
+        };
+
+        let result = write_frame(
+            &mut writer,
+            2,
+            format!("{{\"v\": 1,\"client_id\":\"{}\"}}", self.client_id)
+                .as_bytes(),
+        )
+        .await;
+        let _ = writer.shutdown().await;
+        self.last_activity = None;
+
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+
+        result
+    }
+
+    /// Looks for a socket on a blocking thread, as checking the policy and
+    /// the kind of client reads from the file system.
+    async fn open(&mut self) -> io::Result<UnixStream> {
+        let policy = self.socket_policy;
+        let (pipe, target) =
+            tokio::task::spawn_blocking(move || open_pipe(policy))
+                .await
+                .map_err(io::Error::other)??;
+        pipe.set_nonblocking(true)?;
+        self.target = Some(target);
+
+        UnixStream::from_std(pipe)
+    }
+
+    async fn handshake(&mut self, mut stream: UnixStream) -> io::Result<()> {
+        write_frame(
+            &mut stream,
+            0,
+            format!("{{\"v\": 1,\"client_id\":\"{}\"}}", self.client_id)
+                .as_bytes(),
+        )
+        .await?;
+
+        let (op, data) = read_frame(&mut stream).await?;
+        if op != 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                String::from_utf8_lossy(&data).into_owned(),
+            ));
+        }
+
+        let (reader, writer) = stream.into_split();
+        emit(&self.events, Event::Ready);
+
+        self.writer = Some(writer);
+        self.last_activity = None;
+        self.handle =
+            Some(tokio::spawn(listen(reader, Arc::clone(&self.events))));
+
+        Ok(())
+    }
+
+    /// Forgets the connection if the background reader saw it close.
+    fn drop_lost(&mut self) {
+        if self.handle.as_ref().is_some_and(JoinHandle::is_finished) {
+            self.handle = None;
+            self.writer = None;
+            self.last_activity = None;
+        }
+    }
+
+    async fn write(&mut self, opcode: u32, data: &[u8]) -> io::Result<()> {
+        self.drop_lost();
+        match self.writer.as_mut() {
+            Some(writer) => write_frame(writer, opcode, data).await,
+            None => {
+                Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found"))
+            }
+        }
+    }
+}
+
+impl Drop for AsyncRichClient<'_> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+}
+
+/// Sends `event` to the subscribed stream, if any, forgetting it once it
+/// was dropped.
+fn emit(events: &Mutex<Option<mpsc::UnboundedSender<Event>>>, event: Event) {
+    let mut sender = events.lock().unwrap();
+    if sender
+        .as_ref()
+        .is_some_and(|sender| sender.send(event).is_err())
+    {
+        *sender = None;
+    }
+}
+
+async fn listen(
+    mut reader: OwnedReadHalf,
+    events: Arc<Mutex<Option<mpsc::UnboundedSender<Event>>>>,
+) {
+    loop {
+        match read_frame(&mut reader).await {
+            Ok((1, data)) => emit(
+                &events,
+                Event::Update(String::from_utf8_lossy(&data).into_owned()),
+            ),
+            Ok((2, _)) => {
+                emit(&events, Event::Disconnected);
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                emit(&events, Event::Error(e.to_string()));
+                emit(&events, Event::Disconnected);
+                return;
+            }
+        }
+    }
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    opcode: u32,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut payload = utils::encode(opcode, data.len() as u32);
+    payload.extend_from_slice(data);
+    writer.write_all(&payload).await
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).await?;
+    let (op, len) = utils::decode(&header);
+    let mut buffer = vec![0u8; len as usize];
+    reader.read_exact(&mut buffer).await?;
+    Ok((op, buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::poll_fn;
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    async fn next(events: &mut Events) -> Option<Event> {
+        poll_fn(|cx| Pin::new(&mut *events).poll_next(cx)).await
+    }
+
+    /// Connects `client` to a mock Discord, returning its end of the
+    /// socket once the handshake has been read and acknowledged.
+    async fn connect(client: &mut AsyncRichClient<'_>) -> UnixStream {
+        let (mut discord, pipe) = UnixStream::pair().unwrap();
+        let ready = tokio::spawn(async move {
+            let (op, _) = read_frame(&mut discord).await.unwrap();
+            assert_eq!(op, 0);
+            write_frame(&mut discord, 1, b"{\"evt\":\"READY\"}")
+                .await
+                .unwrap();
+            discord
+        });
+        client.handshake(pipe).await.unwrap();
+        ready.await.unwrap()
+    }
+
+    /// The details of the next activity sent to `discord`.
+    async fn sent(discord: &mut UnixStream) -> Option<String> {
+        let (_, data) = read_frame(discord).await.unwrap();
+        let frame: crate::json::Value =
+            String::from_utf8(data).unwrap().parse().unwrap();
+        frame
+            .get("args")?
+            .get("activity")?
+            .get("details")?
+            .as_str()
+            .map(str::to_string)
+    }
+
+    #[test]
+    fn reconnects_after_disconnect() {
+        block_on(async {
+            let mut client = AsyncRichClient::new(0);
+            let mut events = client.events();
+            let discord = connect(&mut client).await;
+            assert_eq!(next(&mut events).await, Some(Event::Ready));
+            client.update(Activity::new().details("one")).await.unwrap();
+
+            drop(discord);
+            assert!(matches!(next(&mut events).await, Some(Event::Error(_))));
+            assert_eq!(next(&mut events).await, Some(Event::Disconnected));
+            assert!(client
+                .update(Activity::new().details("two"))
+                .await
+                .is_err());
+
+            let mut discord = connect(&mut client).await;
+            assert_eq!(next(&mut events).await, Some(Event::Ready));
+            client.update(Activity::new().details("one")).await.unwrap();
+            assert_eq!(sent(&mut discord).await.as_deref(), Some("one"));
+        });
+    }
+
+    #[test]
+    fn events_are_only_kept_while_subscribed() {
+        let mut client = AsyncRichClient::new(0);
+        emit(&client.events, Event::Ready);
+        let mut events = client.events();
+        emit(&client.events, Event::Disconnected);
+        assert_eq!(block_on(next(&mut events)), Some(Event::Disconnected));
+
+        drop(events);
+        emit(&client.events, Event::Ready);
+        assert!(client.events.lock().unwrap().is_none());
+    }
+}
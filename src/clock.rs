@@ -0,0 +1,221 @@
+use std::{
+    sync::{Arc, Mutex},
+    task::Waker,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::rpc::timestamp::Timestamp;
+use crate::schedule::LocalTime;
+
+/// A source of time for timing behavior: history entries, scene timers,
+/// rate limiting, layer expiry, idle timeouts, carousels, schedules and
+/// reconnect backoff, as well as timeouts on I/O such as waiting for
+/// Discord to respond.
+///
+/// Helpers that read the current time without being given a clock, such
+/// as [`Timestamp::now`] and [`Activity::remaining`], use the system clock
+/// and have variants taking one, like [`Activity::remaining_on`].
+///
+/// [`Activity::remaining`]: crate::rpc::activity::Activity::remaining
+/// [`Activity::remaining_on`]: crate::rpc::activity::Activity::remaining_on
+pub trait Clock: Send + Sync {
+    /// The current monotonic time.
+    fn now(&self) -> Instant;
+
+    /// The current wall-clock time.
+    fn system_time(&self) -> SystemTime;
+
+    /// The current time of the week in the local time zone.
+    fn local_time(&self) -> LocalTime {
+        LocalTime::from_system_time(self.system_time())
+    }
+
+    /// The current wall-clock time as sent to Discord.
+    fn timestamp(&self) -> Timestamp {
+        self.system_time().into()
+    }
+
+    /// How long a thread should block to reach `deadline`, or `None` to
+    /// block until one of the registered wakers is called.
+    fn timeout(&self, deadline: Instant) -> Option<Duration> {
+        Some(deadline.saturating_duration_since(self.now()))
+    }
+
+    /// Registers `waker`, to be called whenever the clock is moved forward
+    /// by other means than time passing, so that threads blocked on a
+    /// deadline check it again.
+    fn register_waker(&self, _waker: Waker) {}
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        (**self).system_time()
+    }
+
+    fn local_time(&self) -> LocalTime {
+        (**self).local_time()
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        (**self).timestamp()
+    }
+
+    fn timeout(&self, deadline: Instant) -> Option<Duration> {
+        (**self).timeout(deadline)
+    }
+
+    fn register_waker(&self, waker: Waker) {
+        (**self).register_waker(waker)
+    }
+}
+
+/// The system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+struct ManualState {
+    elapsed: Duration,
+    wakers: Vec<Waker>,
+}
+
+/// A clock that only moves when advanced, for testing timing behavior
+/// without sleeping.
+///
+/// Clones share the same time, so keep one to advance a clock handed to a
+/// client. Threads waiting on a deadline are woken on every advance. Local
+/// time is read as UTC, so that schedules don't depend on the time zone of
+/// the machine running the tests.
+#[derive(Clone)]
+pub struct ManualClock {
+    instant: Instant,
+    system_time: SystemTime,
+    state: Arc<Mutex<ManualState>>,
+}
+
+impl ManualClock {
+    /// A clock whose wall-clock time starts at `start`.
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            instant: Instant::now(),
+            system_time: start,
+            state: Arc::new(Mutex::new(ManualState {
+                elapsed: Duration::ZERO,
+                wakers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Moves the clock forward by `duration` and wakes up waiting threads.
+    pub fn advance(&self, duration: Duration) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.elapsed += duration;
+            state.wakers.clone()
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// How far the clock has been advanced.
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.instant + self.elapsed()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.system_time + self.elapsed()
+    }
+
+    fn local_time(&self) -> LocalTime {
+        LocalTime::utc(self.system_time())
+    }
+
+    fn timeout(&self, _deadline: Instant) -> Option<Duration> {
+        None
+    }
+
+    fn register_waker(&self, waker: Waker) {
+        self.state.lock().unwrap().wakers.push(waker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::activity::Activity;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Wake,
+        time::UNIX_EPOCH,
+    };
+
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn manual_clock_moves_when_advanced() {
+        // Monday, 2024-01-01 00:00 UTC.
+        let clock =
+            ManualClock::new(UNIX_EPOCH + Duration::from_secs(1704067200));
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        clock.register_waker(Waker::from(Arc::clone(&count)));
+        let start = clock.now();
+        assert_eq!(clock.local_time(), LocalTime::new(0, 0, 0, 0));
+        assert_eq!(clock.timeout(start), None);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.now() - start, Duration::from_secs(90));
+        assert_eq!(clock.elapsed(), Duration::from_secs(90));
+        assert_eq!(clock.timestamp(), Timestamp::from_secs(1704067290));
+        assert_eq!(clock.local_time(), LocalTime::new(0, 0, 1, 30));
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn helpers_read_the_given_clock() {
+        let clock = ManualClock::new(UNIX_EPOCH + Duration::from_secs(1000));
+        let minute = Duration::from_secs(60);
+        assert_eq!(
+            Timestamp::from_instant(clock.now() + minute, &clock),
+            Timestamp::from_secs(1060)
+        );
+        assert_eq!(
+            Timestamp::from_instant(clock.now() - minute, &clock),
+            Timestamp::from_secs(940)
+        );
+
+        let activity = Activity::new().progress_on(&clock, minute, minute * 3);
+        let timestamps = activity.timestamps.unwrap();
+        assert_eq!(timestamps.start, Some(Timestamp::from_secs(940)));
+        assert_eq!(timestamps.end, Some(Timestamp::from_secs(1120)));
+
+        let activity = Activity::new().remaining_on(&clock, minute);
+        let timestamps = activity.timestamps.unwrap();
+        assert_eq!(timestamps.end, Some(Timestamp::from_secs(1060)));
+    }
+}
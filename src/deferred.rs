@@ -0,0 +1,331 @@
+#[cfg(target_os = "linux")]
+use std::{
+    io::{Read, Write},
+    os::{fd::AsRawFd, unix::net::UnixStream},
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    task::{Wake, Waker},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::clock::Clock;
+use crate::history::{Operation, Outcome};
+#[cfg(target_os = "linux")]
+use crate::ipc::platform::inotify::{self, SocketEvent, SocketWatcher};
+use crate::ipc::policy::SocketPolicy;
+use crate::manager::{discover, SharedDiscovery};
+use crate::{ConnectionState, ExitSignal, Listener, RichClient};
+
+/// How often the background connection looks for Discord.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the socket directories are rescanned while they are watched,
+/// in case a change was missed, such as a directory that couldn't be
+/// watched.
+#[cfg(target_os = "linux")]
+const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The presence a client in deferred mode should show once connected.
+pub(crate) struct Deferred {
+    /// The `SET_ACTIVITY` payload to send, or `None` for no presence.
+    pub(crate) desired: Mutex<Option<String>>,
+    stopped: Mutex<bool>,
+    /// Set to end the current wait early, as another session of a
+    /// [`PresenceManager`](crate::PresenceManager) found Discord.
+    retry: AtomicBool,
+    wake: Condvar,
+    /// Wakes up a thread blocked on the socket watcher.
+    #[cfg(target_os = "linux")]
+    waker: Option<(UnixStream, UnixStream)>,
+}
+
+impl Default for Deferred {
+    fn default() -> Self {
+        Self {
+            desired: Mutex::default(),
+            stopped: Mutex::default(),
+            retry: AtomicBool::new(false),
+            wake: Condvar::new(),
+            #[cfg(target_os = "linux")]
+            waker: UnixStream::pair().ok(),
+        }
+    }
+}
+
+impl Deferred {
+    pub(crate) fn stop(&self) {
+        *self.stopped.lock().unwrap() = true;
+        self.wake.notify_all();
+        self.interrupt();
+    }
+
+    /// Ends the current wait for Discord, so that the client looks for it
+    /// again right away.
+    pub(crate) fn retry(&self) {
+        self.retry.store(true, Ordering::SeqCst);
+        let _stopped = self.stopped.lock().unwrap();
+        self.wake.notify_all();
+        self.interrupt();
+    }
+
+    /// Wakes up a thread blocked on the socket watcher.
+    fn interrupt(&self) {
+        #[cfg(target_os = "linux")]
+        if let Some((waker, _)) = self.waker.as_ref() {
+            let _ = (&*waker).write_all(&[0]);
+        }
+    }
+
+    /// Waits until Discord may have started or the client is shut down,
+    /// returning whether it was shut down.
+    ///
+    /// On Linux, this blocks until a socket is created in one of the
+    /// watched directories, or a missing socket directory is created.
+    /// Elsewhere, or if the directories can't be watched, it retries every
+    /// few seconds by `clock`. Either way, [`Deferred::retry`] ends the
+    /// wait early.
+    fn wait(
+        &self,
+        clock: &dyn Clock,
+        #[cfg(target_os = "linux")] watcher: Option<&mut SocketWatcher>,
+    ) -> bool {
+        #[cfg(target_os = "linux")]
+        if let (Some(watcher), Some((_, waker))) =
+            (watcher, self.waker.as_ref())
+        {
+            loop {
+                if *self.stopped.lock().unwrap() {
+                    return true;
+                }
+                if self.retry.swap(false, Ordering::SeqCst) {
+                    return false;
+                }
+                let Ok(revents) = inotify::poll(
+                    &[
+                        (watcher.as_raw_fd(), inotify::POLLIN),
+                        (waker.as_raw_fd(), inotify::POLLIN),
+                    ],
+                    Some(RESCAN_INTERVAL),
+                ) else {
+                    break;
+                };
+                if revents[1] != 0 {
+                    let _ = (&*waker).read(&mut [0]);
+                    continue;
+                }
+                if revents[0] == 0 {
+                    return false;
+                }
+
+                match watcher.read_events() {
+                    Ok(events)
+                        if events.iter().any(|event| {
+                            matches!(
+                                event,
+                                SocketEvent::Created(_)
+                                    | SocketEvent::DirCreated(_)
+                            )
+                        }) =>
+                    {
+                        return false
+                    }
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+        }
+
+        let deadline = clock.now() + RETRY_INTERVAL;
+        let mut stopped = self.stopped.lock().unwrap();
+        loop {
+            if *stopped {
+                return true;
+            }
+            if self.retry.swap(false, Ordering::SeqCst)
+                || clock.now() >= deadline
+            {
+                return false;
+            }
+            stopped = match clock.timeout(deadline) {
+                Some(timeout) => {
+                    self.wake.wait_timeout(stopped, timeout).unwrap().0
+                }
+                None => self.wake.wait(stopped).unwrap(),
+            };
+        }
+    }
+
+    /// Sends the desired presence over a connection that just became
+    /// ready.
+    pub(crate) fn apply(&self, listener: &Listener, target: Option<&str>) {
+        let Some(payload) = self.desired.lock().unwrap().clone() else {
+            return;
+        };
+
+        let result =
+            RichClient::_write(&listener.pipe, 1, Some(payload.as_bytes()));
+        listener.history.lock().unwrap().push(
+            Operation::Update,
+            target,
+            "-",
+            match result {
+                Ok(()) => Outcome::Sent,
+                Err(e) => Outcome::Failed(e.to_string()),
+            },
+        );
+    }
+}
+
+/// Wakes up a wait for the next retry when the clock moves.
+struct RetryWaker(Weak<Deferred>);
+
+impl Wake for RetryWaker {
+    fn wake(self: Arc<Self>) {
+        if let Some(deferred) = self.0.upgrade() {
+            // Taking the lock ensures a waiting thread is either notified or
+            // sees the new time before it blocks.
+            let _stopped = deferred.stopped.lock().unwrap();
+            deferred.wake.notify_all();
+        }
+    }
+}
+
+/// Spawns the thread that connects whenever Discord is running and reads
+/// from the socket while connected, setting `exit` when it returns. Sockets
+/// are found through `discovery` if the client belongs to a
+/// [`PresenceManager`](crate::PresenceManager).
+pub(crate) fn spawn(
+    listener: Listener,
+    deferred: Arc<Deferred>,
+    policy: SocketPolicy,
+    clock: Arc<dyn Clock>,
+    discovery: Option<Arc<SharedDiscovery>>,
+    exit: ExitSignal,
+) -> JoinHandle<Option<String>> {
+    *deferred.stopped.lock().unwrap() = false;
+    if let Some(discovery) = &discovery {
+        discovery.register(&deferred);
+    }
+    clock.register_waker(Waker::from(Arc::new(RetryWaker(Arc::downgrade(
+        &deferred,
+    )))));
+    thread::spawn(move || {
+        let _exit = exit;
+        run(listener, deferred, policy, clock, discovery)
+    })
+}
+
+/// Connects and reads in turn until the client is shut down, returning an
+/// error message if Discord rejected it.
+fn run(
+    listener: Listener,
+    deferred: Arc<Deferred>,
+    policy: SocketPolicy,
+    clock: Arc<dyn Clock>,
+    discovery: Option<Arc<SharedDiscovery>>,
+) -> Option<String> {
+    loop {
+        // Watch before looking for a socket so that none created in between
+        // is missed.
+        #[cfg(target_os = "linux")]
+        let mut watcher = SocketWatcher::new().ok();
+        if let Ok((pipe, target)) = discover(policy, discovery.as_deref()) {
+            #[cfg(target_os = "linux")]
+            if let (Some(watcher), Ok(pipe)) =
+                (watcher.take(), pipe.try_clone())
+            {
+                watcher.watch_removal(target.path.clone(), pipe);
+            }
+            {
+                let stopped = deferred.stopped.lock().unwrap();
+                if *stopped {
+                    return None;
+                }
+                *listener.pipe.write().unwrap() = Some(pipe);
+                *listener.target.write().unwrap() = Some(target);
+                *listener.connection_state.write().unwrap() =
+                    ConnectionState::Connected;
+            }
+
+            if RichClient::_handshake(&listener.pipe, listener.client_id)
+                .is_ok()
+            {
+                if let Some(err) = listener.run() {
+                    return Some(err);
+                }
+            }
+
+            listener.disconnected();
+            *listener.pipe.write().unwrap() = None;
+            #[cfg(target_os = "linux")]
+            {
+                watcher = SocketWatcher::new().ok();
+            }
+        }
+
+        if deferred.wait(
+            clock.as_ref(),
+            #[cfg(target_os = "linux")]
+            watcher.as_mut(),
+        ) {
+            return None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::{sync::mpsc, time::SystemTime};
+
+    /// Waits for the next retry on another thread, returning whether the
+    /// wait ended because the client was shut down.
+    fn wait(
+        deferred: &Arc<Deferred>,
+        clock: &ManualClock,
+    ) -> mpsc::Receiver<bool> {
+        clock.register_waker(Waker::from(Arc::new(RetryWaker(
+            Arc::downgrade(deferred),
+        ))));
+        let (tx, rx) = mpsc::channel();
+        let deferred = Arc::clone(deferred);
+        let clock = clock.clone();
+        thread::spawn(move || {
+            let _ = tx.send(deferred.wait(
+                &clock,
+                #[cfg(target_os = "linux")]
+                None,
+            ));
+        });
+        // Let the thread read the clock before it is moved.
+        thread::sleep(Duration::from_millis(50));
+        rx
+    }
+
+    #[test]
+    fn retries_after_interval() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let deferred = Arc::new(Deferred::default());
+        let retried = wait(&deferred, &clock);
+
+        clock.advance(RETRY_INTERVAL - Duration::from_secs(1));
+        assert!(retried.recv_timeout(Duration::from_millis(100)).is_err());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(retried.recv_timeout(Duration::from_secs(5)), Ok(false));
+    }
+
+    #[test]
+    fn stop_ends_wait() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let deferred = Arc::new(Deferred::default());
+        let stopped = wait(&deferred, &clock);
+
+        deferred.stop();
+        assert_eq!(stopped.recv_timeout(Duration::from_secs(5)), Ok(true));
+    }
+}
@@ -0,0 +1,955 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    task::{Wake, Waker},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::clock::Clock;
+use crate::rpc::{activity::Activity, command::Command, timestamp::Timestamp};
+use crate::scene::{Scenes, Vars};
+use crate::schedule::{self, Rule, Schedule};
+use crate::RichClient;
+
+/// Discord ignores activity updates beyond this many per
+/// [`RATE_LIMIT_WINDOW`].
+const RATE_LIMIT: usize = 5;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(20);
+/// The shortest interval a carousel rotates at, which keeps it within the
+/// rate limit on its own.
+const MIN_CAROUSEL_INTERVAL: Duration =
+    Duration::from_secs(RATE_LIMIT_WINDOW.as_secs() / RATE_LIMIT as u64);
+
+enum Request {
+    Update(String),
+    Clear,
+    /// Re-evaluates which activity should be shown.
+    Refresh,
+    Shutdown,
+}
+
+/// An activity pushed with [`RichClientHandle::push_layer`].
+struct LayerEntry {
+    id: u64,
+    priority: i32,
+    payload: String,
+    expires: Option<Instant>,
+}
+
+/// Activities rotated with [`RichClientHandle::start_carousel`].
+struct CarouselEntry {
+    id: u64,
+    payloads: Vec<String>,
+    index: usize,
+    interval: Duration,
+    /// When to rotate next, or `None` while paused.
+    next: Option<Instant>,
+    start: Option<Timestamp>,
+}
+
+impl CarouselEntry {
+    /// Moves on to the next activity if the carousel is due to rotate at
+    /// `now`.
+    fn rotate(&mut self, now: Instant) {
+        if self.next.is_some_and(|next| next <= now) {
+            self.index = (self.index + 1) % self.payloads.len().max(1);
+            self.next = Some(now + self.interval);
+        }
+    }
+}
+
+/// What to show once the handle hasn't been touched for `timeout`.
+struct Idle {
+    timeout: Duration,
+    /// `None` to clear the presence.
+    payload: Option<String>,
+    last_touch: Instant,
+}
+
+impl Idle {
+    fn is_idle(&self, now: Instant) -> bool {
+        now.duration_since(self.last_touch) >= self.timeout
+    }
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<Request>>,
+    signal: Condvar,
+    error: Mutex<Option<io::Error>>,
+    layers: Mutex<Vec<LayerEntry>>,
+    next_layer: AtomicU64,
+    carousel: Mutex<Option<CarouselEntry>>,
+    next_carousel: AtomicU64,
+    /// The rules of the schedule, with their activities serialized.
+    schedule: Mutex<Option<Vec<Rule<Option<String>>>>>,
+    idle: Mutex<Option<Idle>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Shared {
+    fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            queue: Mutex::default(),
+            signal: Condvar::new(),
+            error: Mutex::default(),
+            layers: Mutex::default(),
+            next_layer: AtomicU64::default(),
+            carousel: Mutex::default(),
+            next_carousel: AtomicU64::default(),
+            schedule: Mutex::default(),
+            idle: Mutex::default(),
+            clock,
+        }
+    }
+
+    fn push(&self, request: Request) {
+        let mut queue = self.queue.lock().unwrap();
+        match request {
+            Request::Update(_) | Request::Clear => {
+                queue.retain(|request| !matches!(request, Request::Update(_)));
+                queue.push_back(request);
+            }
+            Request::Refresh => {
+                if !queue
+                    .iter()
+                    .any(|request| matches!(request, Request::Refresh))
+                {
+                    queue.push_back(request);
+                }
+            }
+            Request::Shutdown => queue.push_front(request),
+        }
+        self.signal.notify_one();
+    }
+
+    /// Waits for the next request, or returns `None` once `deadline`
+    /// passes.
+    fn pop(&self, deadline: Option<Instant>) -> Option<Request> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if let Some(request) = queue.pop_front() {
+                return Some(request);
+            }
+            let timeout = match deadline {
+                Some(deadline) => {
+                    if self.clock.now() >= deadline {
+                        return None;
+                    }
+                    self.clock.timeout(deadline)
+                }
+                None => None,
+            };
+            queue = match timeout {
+                Some(timeout) => {
+                    self.signal.wait_timeout(queue, timeout).unwrap().0
+                }
+                None => self.signal.wait(queue).unwrap(),
+            };
+        }
+    }
+
+    /// Drops expired layers and returns the payload of the one with the
+    /// highest priority, preferring the most recently pushed on ties.
+    fn winner(&self) -> Option<String> {
+        let mut layers = self.layers.lock().unwrap();
+        let now = self.clock.now();
+        layers
+            .retain(|layer| layer.expires.is_none_or(|expires| expires > now));
+        layers
+            .iter()
+            .max_by_key(|layer| (layer.priority, layer.id))
+            .map(|layer| layer.payload.clone())
+    }
+
+    /// Rotates the carousel if it is due and returns the payload of its
+    /// current activity.
+    fn carousel_payload(&self) -> Option<String> {
+        let mut carousel = self.carousel.lock().unwrap();
+        let carousel = carousel.as_mut()?;
+        carousel.rotate(self.clock.now());
+
+        carousel.payloads.get(carousel.index).cloned()
+    }
+
+    /// What the schedule shows now, or `None` if no rule covers it.
+    fn schedule_payload(&self) -> Option<Option<String>> {
+        let rules = self.schedule.lock().unwrap();
+        schedule::active(rules.as_ref()?, self.clock.local_time()).cloned()
+    }
+
+    /// What to show while idle, or `None` if not idle.
+    fn idle_payload(&self) -> Option<Option<String>> {
+        self.idle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|idle| idle.is_idle(self.clock.now()))
+            .map(|idle| idle.payload.clone())
+    }
+
+    /// When the shown activity may change next without a request: a layer
+    /// expiring, the carousel rotating, a scheduled window starting or
+    /// ending, or the handle becoming idle. Deadlines up to `evaluated`,
+    /// when the shown activity was last worked out, have been accounted
+    /// for already. A carousel hidden behind something else keeps rotating
+    /// in the background.
+    fn next_deadline(&self, evaluated: Instant) -> Option<Instant> {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|idle| idle.last_touch + idle.timeout);
+        let carousel =
+            self.carousel.lock().unwrap().as_mut().and_then(|carousel| {
+                carousel.rotate(evaluated);
+                carousel.next
+            });
+        let schedule =
+            self.schedule.lock().unwrap().as_ref().and_then(|rules| {
+                schedule::next_change(rules, self.clock.local_time())
+                    .map(|change| self.clock.now() + change)
+            });
+        self.layers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|layer| layer.expires)
+            .chain(idle)
+            .chain(schedule)
+            .chain(carousel)
+            .filter(|&deadline| deadline > evaluated)
+            .min()
+    }
+
+    /// Updates the carousel `id`, if it is still the running one.
+    fn with_carousel(&self, id: u64, f: impl FnOnce(&mut CarouselEntry)) {
+        if let Some(carousel) = self
+            .carousel
+            .lock()
+            .unwrap()
+            .as_mut()
+            .filter(|carousel| carousel.id == id)
+        {
+            f(carousel);
+        }
+        self.push(Request::Refresh);
+    }
+}
+
+/// Tracks recent activity updates to stay within Discord's rate limit.
+#[derive(Default)]
+struct RateLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    /// When the next update may be sent, or `None` if it may be sent now.
+    fn next_slot(&mut self, now: Instant) -> Option<Instant> {
+        while self
+            .sent
+            .front()
+            .is_some_and(|&sent| now.duration_since(sent) >= RATE_LIMIT_WINDOW)
+        {
+            self.sent.pop_front();
+        }
+
+        (self.sent.len() >= RATE_LIMIT)
+            .then(|| self.sent[0] + RATE_LIMIT_WINDOW)
+    }
+
+    fn record(&mut self, now: Instant) {
+        self.sent.push_back(now);
+    }
+}
+
+/// Wakes up the worker when the clock moves, so that it checks its
+/// deadlines again.
+struct WorkerWaker(Weak<Shared>);
+
+impl Wake for WorkerWaker {
+    fn wake(self: Arc<Self>) {
+        if let Some(shared) = self.0.upgrade() {
+            // Taking the lock ensures the worker is either notified or sees
+            // the new time before it blocks.
+            let _queue = shared.queue.lock().unwrap();
+            shared.signal.notify_one();
+        }
+    }
+}
+
+struct Inner {
+    pid: u32,
+    shared: Arc<Shared>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+    scenes: Mutex<Scenes>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.shared.push(Request::Shutdown);
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A cloneable, thread-safe handle to a [`RichClient`].
+///
+/// The client is moved onto a worker thread that owns the socket and
+/// executes requests in order. Pending updates are coalesced, so only the
+/// most recent one is sent, and a clear discards any update queued before
+/// it. Errors raised by the worker are returned from the next call.
+///
+/// Besides the activity set with [`RichClientHandle::update`], other parts
+/// of an application can push layers on top of it with
+/// [`RichClientHandle::push_layer`]. The worker always shows the live layer
+/// with the highest priority, falling back to the updated activity, and
+/// only sends it when it changes.
+///
+/// A carousel started with [`RichClientHandle::start_carousel`] rotates
+/// through a list of activities in place of the updated one, on the same
+/// worker thread.
+///
+/// A [`Schedule`] set with [`RichClientHandle::set_schedule`] overrides the
+/// carousel and the updated activity during its windows, and is applied as
+/// they start and end.
+///
+/// With [`RichClientHandle::set_idle`], the handle switches to an idle
+/// activity, or clears the presence, when [`RichClientHandle::touch`] hasn't
+/// been called for a while. The next touch restores what was shown before,
+/// including its original start timestamp. Idle tracking needs the worker
+/// thread to switch presence on time, so it is only available on the
+/// handle, not on [`RichClient`] itself.
+///
+/// Updates are limited to 5 every 20 seconds, as Discord ignores the rest.
+/// Changes beyond that are held back and only the latest is sent once the
+/// limit allows it.
+///
+/// The worker shuts the client down once the last handle is dropped.
+#[derive(Clone)]
+pub struct RichClientHandle {
+    inner: Arc<Inner>,
+}
+
+impl RichClientHandle {
+    pub(crate) fn new(mut client: RichClient<'static>) -> Self {
+        let pid = client.pid;
+        let shared = Arc::new(Shared::new(Arc::clone(&client.clock)));
+        shared
+            .clock
+            .register_waker(Waker::from(Arc::new(WorkerWaker(
+                Arc::downgrade(&shared),
+            ))));
+        let worker = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                let mut base = None;
+                let mut limiter = RateLimiter::default();
+                let mut held_until = None;
+                let mut evaluated = shared.clock.now();
+                loop {
+                    let deadline =
+                        match (shared.next_deadline(evaluated), held_until) {
+                            (Some(deadline), Some(held_until)) => {
+                                Some(Instant::min(deadline, held_until))
+                            }
+                            (deadline, held_until) => deadline.or(held_until),
+                        };
+                    match shared.pop(deadline) {
+                        Some(Request::Update(payload)) => base = Some(payload),
+                        Some(Request::Clear) => base = None,
+                        Some(Request::Refresh) | None => {}
+                        Some(Request::Shutdown) => {
+                            client.clear_on_shutdown();
+                            let _ = client.shutdown();
+                            return;
+                        }
+                    }
+                    evaluated = shared.clock.now();
+
+                    let payload = shared.idle_payload().unwrap_or_else(|| {
+                        shared.winner().map(Some).unwrap_or_else(|| {
+                            shared.schedule_payload().unwrap_or_else(|| {
+                                shared
+                                    .carousel_payload()
+                                    .or_else(|| base.clone())
+                            })
+                        })
+                    });
+                    if payload == client.last_activity {
+                        held_until = None;
+                        continue;
+                    }
+                    held_until = limiter.next_slot(shared.clock.now());
+                    if held_until.is_some() {
+                        continue;
+                    }
+                    limiter.record(shared.clock.now());
+                    let result = match &payload {
+                        Some(payload) => client.send_payload(payload),
+                        None => client.clear(),
+                    };
+                    if let Err(e) = result {
+                        *shared.error.lock().unwrap() = Some(e);
+                    }
+                }
+            })
+        };
+
+        Self {
+            inner: Arc::new(Inner {
+                pid,
+                shared,
+                worker: Mutex::new(Some(worker)),
+                scenes: Mutex::default(),
+            }),
+        }
+    }
+
+    pub fn update(&self, activity: &Activity) -> io::Result<()> {
+        self.take_error()?;
+        self.inner
+            .shared
+            .push(Request::Update(payload(self.inner.pid, activity)));
+
+        Ok(())
+    }
+
+    pub fn clear(&self) -> io::Result<()> {
+        self.take_error()?;
+        self.inner.shared.push(Request::Clear);
+
+        Ok(())
+    }
+
+    /// Shows `activity` over the updated one while it has the highest
+    /// priority of all live layers. The layer is retracted when the
+    /// returned [`Layer`] is dropped, or after `ttl` if one is given.
+    pub fn push_layer(
+        &self,
+        priority: i32,
+        activity: &Activity,
+        ttl: Option<Duration>,
+    ) -> io::Result<Layer> {
+        self.take_error()?;
+        let shared = &self.inner.shared;
+        let id = shared.next_layer.fetch_add(1, Ordering::Relaxed);
+        shared.layers.lock().unwrap().push(LayerEntry {
+            id,
+            priority,
+            payload: payload(self.inner.pid, activity),
+            expires: ttl.map(|ttl| shared.clock.now() + ttl),
+        });
+        shared.push(Request::Refresh);
+
+        Ok(Layer {
+            shared: Arc::downgrade(shared),
+            pid: self.inner.pid,
+            id,
+        })
+    }
+
+    /// Rotates through `activities` every `interval`, showing them instead
+    /// of the updated activity until the returned [`Carousel`] is dropped.
+    /// Starting another carousel replaces this one.
+    ///
+    /// The interval is at least 4 seconds, so that the carousel alone stays
+    /// within the rate limit. If `start` is given, every activity shows it
+    /// as their start timestamp, so the elapsed time doesn't reset on each
+    /// rotation.
+    pub fn start_carousel(
+        &self,
+        activities: &[Activity],
+        interval: Duration,
+        start: Option<Timestamp>,
+    ) -> io::Result<Carousel> {
+        self.take_error()?;
+        let shared = &self.inner.shared;
+        let id = shared.next_carousel.fetch_add(1, Ordering::Relaxed);
+        let interval = interval.max(MIN_CAROUSEL_INTERVAL);
+        *shared.carousel.lock().unwrap() = Some(CarouselEntry {
+            id,
+            payloads: payloads(self.inner.pid, activities, start),
+            index: 0,
+            interval,
+            next: Some(shared.clock.now() + interval),
+            start,
+        });
+        shared.push(Request::Refresh);
+
+        Ok(Carousel {
+            shared: Arc::downgrade(shared),
+            pid: self.inner.pid,
+            id,
+        })
+    }
+
+    /// Applies `schedule` in the background, replacing any previous one.
+    /// While one of its windows is active, it decides what is shown unless
+    /// a layer or the idle activity takes precedence.
+    pub fn set_schedule(&self, schedule: &Schedule) -> io::Result<()> {
+        self.take_error()?;
+        let rules = schedule
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.map(|activity| {
+                    activity
+                        .as_ref()
+                        .map(|activity| payload(self.inner.pid, activity))
+                })
+            })
+            .collect();
+        *self.inner.shared.schedule.lock().unwrap() = Some(rules);
+        self.inner.shared.push(Request::Refresh);
+
+        Ok(())
+    }
+
+    /// Stops applying the schedule.
+    pub fn clear_schedule(&self) {
+        *self.inner.shared.schedule.lock().unwrap() = None;
+        self.inner.shared.push(Request::Refresh);
+    }
+
+    /// Replaces the scenes [`RichClientHandle::enter_scene`] switches
+    /// between.
+    pub fn set_scenes(&self, mut scenes: Scenes) {
+        scenes.clock = Arc::clone(&self.inner.shared.clock);
+        *self.inner.scenes.lock().unwrap() = scenes;
+    }
+
+    /// Switches to the scene `name` and updates the presence with its
+    /// activity, built from `vars`. See [`Scenes::enter`].
+    pub fn enter_scene(&self, name: &str, vars: &Vars) -> io::Result<()> {
+        let activity = self.inner.scenes.lock().unwrap().enter(name, vars)?;
+        self.update(&activity)
+    }
+
+    /// Shows `activity`, or clears the presence if it is `None`, once the
+    /// handle hasn't been touched for `timeout`. Counts as a touch.
+    pub fn set_idle(&self, timeout: Duration, activity: Option<&Activity>) {
+        *self.inner.shared.idle.lock().unwrap() = Some(Idle {
+            timeout,
+            payload: activity.map(|activity| payload(self.inner.pid, activity)),
+            last_touch: self.inner.shared.clock.now(),
+        });
+        self.inner.shared.push(Request::Refresh);
+    }
+
+    /// Stops switching to the idle activity, restoring the previous one if
+    /// the handle is currently idle.
+    pub fn disable_idle(&self) {
+        *self.inner.shared.idle.lock().unwrap() = None;
+        self.inner.shared.push(Request::Refresh);
+    }
+
+    /// Records user activity, leaving the idle state if the handle was
+    /// idle.
+    pub fn touch(&self) {
+        let mut idle = self.inner.shared.idle.lock().unwrap();
+        if let Some(idle) = idle.as_mut() {
+            let now = self.inner.shared.clock.now();
+            let was_idle = idle.is_idle(now);
+            idle.last_touch = now;
+            if was_idle {
+                self.inner.shared.push(Request::Refresh);
+            }
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.inner
+            .shared
+            .idle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|idle| idle.is_idle(self.inner.shared.clock.now()))
+    }
+
+    fn take_error(&self) -> io::Result<()> {
+        match self.inner.shared.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A layer of presence pushed with [`RichClientHandle::push_layer`].
+/// Dropping it retracts the layer.
+pub struct Layer {
+    shared: Weak<Shared>,
+    pid: u32,
+    id: u64,
+}
+
+impl Layer {
+    /// Replaces the activity of this layer, keeping its priority and
+    /// expiry. Does nothing if the layer has expired.
+    pub fn update(&self, activity: &Activity) {
+        let Some(shared) = self.shared.upgrade() else {
+            return;
+        };
+        if let Some(layer) = shared
+            .layers
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|layer| layer.id == self.id)
+        {
+            layer.payload = payload(self.pid, activity);
+        }
+        shared.push(Request::Refresh);
+    }
+
+    /// Retracts the layer. Equivalent to dropping it.
+    pub fn retract(self) {}
+}
+
+impl Drop for Layer {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared
+                .layers
+                .lock()
+                .unwrap()
+                .retain(|layer| layer.id != self.id);
+            shared.push(Request::Refresh);
+        }
+    }
+}
+
+/// A carousel started with [`RichClientHandle::start_carousel`]. Dropping
+/// it stops the carousel.
+pub struct Carousel {
+    shared: Weak<Shared>,
+    pid: u32,
+    id: u64,
+}
+
+impl Carousel {
+    /// Keeps showing the current activity until resumed.
+    pub fn pause(&self) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.with_carousel(self.id, |carousel| carousel.next = None);
+        }
+    }
+
+    /// Resumes rotating, a full interval after now.
+    pub fn resume(&self) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.with_carousel(self.id, |carousel| {
+                if carousel.next.is_none() {
+                    carousel.next =
+                        Some(shared.clock.now() + carousel.interval);
+                }
+            });
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.upgrade().is_some_and(|shared| {
+            shared
+                .carousel
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|carousel| {
+                    carousel.id == self.id && carousel.next.is_none()
+                })
+        })
+    }
+
+    /// Replaces the activities, starting over from the first one. Keeps
+    /// the shared start timestamp, if any.
+    pub fn set_activities(&self, activities: &[Activity]) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.with_carousel(self.id, |carousel| {
+                carousel.payloads =
+                    payloads(self.pid, activities, carousel.start);
+                carousel.index = 0;
+                if carousel.next.is_some() {
+                    carousel.next =
+                        Some(shared.clock.now() + carousel.interval);
+                }
+            });
+        }
+    }
+
+    /// Changes how often the carousel rotates, starting from now.
+    pub fn set_interval(&self, interval: Duration) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.with_carousel(self.id, |carousel| {
+                carousel.interval = interval.max(MIN_CAROUSEL_INTERVAL);
+                if carousel.next.is_some() {
+                    carousel.next =
+                        Some(shared.clock.now() + carousel.interval);
+                }
+            });
+        }
+    }
+
+    /// Stops the carousel. Equivalent to dropping it.
+    pub fn stop(self) {}
+}
+
+impl Drop for Carousel {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.upgrade() {
+            let mut carousel = shared.carousel.lock().unwrap();
+            if carousel
+                .as_ref()
+                .is_some_and(|carousel| carousel.id == self.id)
+            {
+                *carousel = None;
+            }
+            drop(carousel);
+            shared.push(Request::Refresh);
+        }
+    }
+}
+
+fn payloads(
+    pid: u32,
+    activities: &[Activity],
+    start: Option<Timestamp>,
+) -> Vec<String> {
+    activities
+        .iter()
+        .map(|activity| match start {
+            Some(start) => payload(pid, &activity.clone().start_time(start)),
+            None => payload(pid, activity),
+        })
+        .collect()
+}
+
+fn payload(pid: u32, activity: &Activity) -> String {
+    Command::SetActivity {
+        pid,
+        activity: Some(activity),
+    }
+    .to_json("-")
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::time::SystemTime;
+    #[cfg(not(target_os = "windows"))]
+    use std::{io::Read, os::unix::net::UnixStream};
+
+    fn shared(clock: &ManualClock) -> Shared {
+        Shared::new(Arc::new(clock.clone()))
+    }
+
+    /// A handle whose client writes to the returned socket.
+    #[cfg(not(target_os = "windows"))]
+    fn handle(clock: &ManualClock) -> (RichClientHandle, UnixStream) {
+        let client = RichClient::new(0).clock(clock.clone());
+        let (discord, pipe) = UnixStream::pair().unwrap();
+        *client.pipe.write().unwrap() = Some(pipe);
+        (client.into_handle(), discord)
+    }
+
+    /// The details of the next activity the worker sends within `timeout`,
+    /// `Some(None)` for a clear, or `None` if nothing is sent.
+    #[cfg(not(target_os = "windows"))]
+    fn sent(
+        discord: &mut UnixStream,
+        timeout: Duration,
+    ) -> Option<Option<String>> {
+        discord.set_read_timeout(Some(timeout)).unwrap();
+        let mut header = [0; 8];
+        discord.read_exact(&mut header).ok()?;
+        let (_, len) = crate::ipc::utils::decode(&header);
+        let mut data = vec![0; len as usize];
+        discord.read_exact(&mut data).unwrap();
+        let frame: crate::json::Value =
+            String::from_utf8(data).unwrap().parse().unwrap();
+        let args = frame.get("args")?;
+        Some(
+            args.get("activity")
+                .and_then(|activity| activity.get("details"))
+                .and_then(|details| details.as_str())
+                .map(str::to_string),
+        )
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn next(discord: &mut UnixStream) -> Option<String> {
+        sent(discord, Duration::from_secs(5)).expect("nothing was sent")
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn nothing_sent(discord: &mut UnixStream) -> bool {
+        sent(discord, Duration::from_millis(100)).is_none()
+    }
+
+    #[test]
+    fn hidden_carousel_keeps_rotating() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let shared = shared(&clock);
+        let interval = Duration::from_secs(5);
+        *shared.carousel.lock().unwrap() = Some(CarouselEntry {
+            id: 0,
+            payloads: vec!["a".into(), "b".into()],
+            index: 0,
+            interval,
+            next: Some(clock.now() + interval),
+            start: None,
+        });
+        shared.layers.lock().unwrap().push(LayerEntry {
+            id: 0,
+            priority: 0,
+            payload: "layer".into(),
+            expires: None,
+        });
+
+        clock.advance(interval * 2);
+        let evaluated = clock.now();
+        assert_eq!(shared.winner().as_deref(), Some("layer"));
+        let deadline = shared.next_deadline(evaluated);
+        assert_eq!(deadline, Some(evaluated + interval));
+
+        shared.layers.lock().unwrap().clear();
+        assert_eq!(shared.carousel_payload().as_deref(), Some("b"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn layer_expires_after_ttl() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let (handle, mut discord) = handle(&clock);
+        handle.update(&Activity::new().details("base")).unwrap();
+        assert_eq!(next(&mut discord).as_deref(), Some("base"));
+
+        let _layer = handle
+            .push_layer(
+                0,
+                &Activity::new().details("layer"),
+                Some(Duration::from_secs(10)),
+            )
+            .unwrap();
+        assert_eq!(next(&mut discord).as_deref(), Some("layer"));
+
+        clock.advance(Duration::from_secs(9));
+        assert!(nothing_sent(&mut discord));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(next(&mut discord).as_deref(), Some("base"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn idle_until_touched() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let (handle, mut discord) = handle(&clock);
+        handle.update(&Activity::new().details("base")).unwrap();
+        assert_eq!(next(&mut discord).as_deref(), Some("base"));
+
+        let timeout = Duration::from_secs(60);
+        handle.set_idle(timeout, Some(&Activity::new().details("away")));
+        clock.advance(timeout / 2);
+        handle.touch();
+        clock.advance(timeout / 2);
+        assert!(!handle.is_idle());
+        assert!(nothing_sent(&mut discord));
+
+        clock.advance(timeout / 2);
+        assert_eq!(next(&mut discord).as_deref(), Some("away"));
+        assert!(handle.is_idle());
+
+        handle.touch();
+        assert_eq!(next(&mut discord).as_deref(), Some("base"));
+        assert!(!handle.is_idle());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn carousel_rotates() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let (handle, mut discord) = handle(&clock);
+        let interval = Duration::from_secs(10);
+        let carousel = handle
+            .start_carousel(
+                &[
+                    Activity::new().details("one"),
+                    Activity::new().details("two"),
+                ],
+                interval,
+                None,
+            )
+            .unwrap();
+        assert_eq!(next(&mut discord).as_deref(), Some("one"));
+
+        clock.advance(interval);
+        assert_eq!(next(&mut discord).as_deref(), Some("two"));
+        clock.advance(interval);
+        assert_eq!(next(&mut discord).as_deref(), Some("one"));
+
+        carousel.pause();
+        clock.advance(interval);
+        assert!(nothing_sent(&mut discord));
+        carousel.resume();
+        clock.advance(interval);
+        assert_eq!(next(&mut discord).as_deref(), Some("two"));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn clears_on_drop() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let client = RichClient::new(0).clock(clock).clear_on_drop(true);
+        let (mut discord, pipe) = UnixStream::pair().unwrap();
+        *client.pipe.write().unwrap() = Some(pipe);
+        *client.connection_state.write().unwrap() =
+            crate::ConnectionState::SentHandshake;
+        let handle = client.into_handle();
+        handle.update(&Activity::new().details("base")).unwrap();
+        assert_eq!(next(&mut discord).as_deref(), Some("base"));
+
+        drop(handle);
+        assert_eq!(next(&mut discord), None);
+    }
+
+    #[test]
+    fn deferred_updates_wait_for_discord() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let handle = RichClient::new(0)
+            .clock(clock.clone())
+            .deferred(true)
+            .into_handle();
+        handle.update(&Activity::new().details("one")).unwrap();
+        handle.update(&Activity::new().details("two")).unwrap();
+        // Let the worker try to send before checking for its error.
+        thread::sleep(Duration::from_millis(50));
+        assert!(handle.take_error().is_ok());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn updates_are_rate_limited() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let (handle, mut discord) = handle(&clock);
+        for details in ["1", "2", "3", "4", "5"] {
+            handle.update(&Activity::new().details(details)).unwrap();
+            assert_eq!(next(&mut discord).as_deref(), Some(details));
+        }
+
+        handle.update(&Activity::new().details("6")).unwrap();
+        handle.update(&Activity::new().details("7")).unwrap();
+        clock.advance(RATE_LIMIT_WINDOW - Duration::from_secs(1));
+        assert!(nothing_sent(&mut discord));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(next(&mut discord).as_deref(), Some("7"));
+        assert!(nothing_sent(&mut discord));
+    }
+}
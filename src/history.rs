@@ -0,0 +1,97 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::clock::{Clock, SystemClock};
+use crate::rpc::ack::AckedActivity;
+use crate::rpc::timestamp::Timestamp;
+
+/// How many entries [`RichClient::history`](crate::RichClient::history)
+/// keeps unless configured otherwise.
+pub(crate) const DEFAULT_CAPACITY: usize = 32;
+
+/// A presence operation recorded by the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub time: Timestamp,
+    pub operation: Operation,
+    /// The socket or pipe the client was connected to, if any.
+    pub target: Option<String>,
+    /// The nonce of the `SET_ACTIVITY` frame. Updates sent through a
+    /// [`RichClientHandle`](crate::RichClientHandle) use `-`.
+    pub nonce: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Update,
+    Clear,
+    /// Discord's response to an update or clear.
+    Ack,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The frame was written to the socket.
+    Sent,
+    /// Discord stored this activity, or `None` if it was cleared.
+    Acked(Option<Box<AckedActivity>>),
+    /// Writing failed or Discord responded with an error.
+    Failed(String),
+}
+
+/// A ring buffer of the most recent presence operations, along with the
+/// activity Discord last acknowledged.
+pub(crate) struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    pub(crate) current: Option<AckedActivity>,
+    /// Timestamps the entries.
+    pub(crate) clock: Arc<dyn Clock>,
+}
+
+impl History {
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        operation: Operation,
+        target: Option<&str>,
+        nonce: &str,
+        outcome: Outcome,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(HistoryEntry {
+            time: self.clock.timestamp(),
+            operation,
+            target: target.map(str::to_string),
+            nonce: nonce.to_string(),
+            outcome,
+        });
+    }
+
+    pub(crate) fn entries(&self) -> Vec<HistoryEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::with_capacity(DEFAULT_CAPACITY),
+            capacity: DEFAULT_CAPACITY,
+            current: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
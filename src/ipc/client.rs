@@ -1,6 +1,7 @@
 use std::io::{self, Read, Write};
+use std::sync::RwLock;
 
-use crate::RichClient;
+use crate::{ClientKind, RichClient};
 
 use super::utils;
 
@@ -10,17 +11,9 @@ impl<'a> RichClient<'a> {
         opcode: u32,
         data: Option<&[u8]>,
     ) -> io::Result<()> {
-        self.pipe.as_ref().as_ref().map_or(
+        self.pipe.read().unwrap().as_ref().map_or(
             Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found")),
             |mut pipe| {
-                println!(
-                    "Sending packet: op={:?}; data={:?}",
-                    opcode,
-                    match data {
-                        Some(data) => String::from_utf8(data.to_vec()).unwrap(),
-                        None => "None".to_string(),
-                    }
-                );
                 let payload = match data {
                     Some(packet) => {
                         let mut payload =
@@ -36,14 +29,14 @@ impl<'a> RichClient<'a> {
     }
 
     pub(crate) fn _write(
-        #[cfg(target_os = "windows")] pipe: &Option<std::fs::File>,
-        #[cfg(not(target_os = "windows"))] pipe: &mut Option<
-            std::os::unix::net::UnixStream,
+        #[cfg(target_os = "windows")] pipe: &RwLock<Option<std::fs::File>>,
+        #[cfg(not(target_os = "windows"))] pipe: &RwLock<
+            Option<std::os::unix::net::UnixStream>,
         >,
         opcode: u32,
         data: Option<&[u8]>,
     ) -> io::Result<()> {
-        pipe.as_ref().map_or(
+        pipe.read().unwrap().as_ref().map_or(
             Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found")),
             |mut pipe| {
                 let payload = match data {
@@ -62,12 +55,12 @@ impl<'a> RichClient<'a> {
     }
 
     pub(crate) fn read(
-        #[cfg(target_os = "windows")] pipe: &Option<std::fs::File>,
-        #[cfg(not(target_os = "windows"))] pipe: &Option<
-            std::os::unix::net::UnixStream,
+        #[cfg(target_os = "windows")] pipe: &RwLock<Option<std::fs::File>>,
+        #[cfg(not(target_os = "windows"))] pipe: &RwLock<
+            Option<std::os::unix::net::UnixStream>,
         >,
     ) -> io::Result<(u32, Vec<u8>)> {
-        pipe.as_ref().map_or(
+        pipe.read().unwrap().as_ref().map_or(
             Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found")),
             |mut pipe| {
                 let mut header = [0; 8];
@@ -81,13 +74,22 @@ impl<'a> RichClient<'a> {
     }
 }
 
+/// The socket or pipe a client connected to.
+pub(crate) struct Target {
+    pub(crate) kind: ClientKind,
+    pub(crate) path: String,
+    /// The sockets [`SocketPolicy`](super::policy::SocketPolicy) skipped
+    /// before this one was found, each with the reason.
+    pub(crate) skipped: Vec<String>,
+}
+
 pub trait Connection {
     fn open(&mut self) -> io::Result<()>;
     fn close(&mut self) -> io::Result<()>;
     fn _close(
-        #[cfg(target_os = "windows")] pipe: &Option<std::fs::File>,
-        #[cfg(not(target_os = "windows"))] pipe: &Option<
-            std::os::unix::net::UnixStream,
+        #[cfg(target_os = "windows")] pipe: &RwLock<Option<std::fs::File>>,
+        #[cfg(not(target_os = "windows"))] pipe: &RwLock<
+            Option<std::os::unix::net::UnixStream>,
         >,
         client_id: u64,
     ) -> io::Result<()>;
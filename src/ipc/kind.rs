@@ -0,0 +1,24 @@
+/// The kind of client serving the socket the client connected to.
+///
+/// Reported by [`RichClient::client_kind`](crate::RichClient::client_kind).
+/// Sockets in Discord's default location are identified by the process
+/// serving them where possible, since other clients use it too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientKind {
+    Discord,
+    Canary,
+    Vesktop,
+    /// The standalone arRPC server.
+    ArRpc,
+}
+
+impl ClientKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientKind::Discord => "Discord",
+            ClientKind::Canary => "Discord Canary",
+            ClientKind::Vesktop => "Vesktop",
+            ClientKind::ArRpc => "arRPC",
+        }
+    }
+}
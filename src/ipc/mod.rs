@@ -1,3 +1,5 @@
 pub mod client;
+pub mod kind;
 pub mod platform;
-mod utils;
+pub mod policy;
+pub(crate) mod utils;
@@ -0,0 +1,298 @@
+use std::ffi::{c_char, c_int, c_short, c_ulong, CString};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::ipc::platform::unix_connection::socket_dirs;
+
+const IN_NONBLOCK: c_int = 0o4000;
+const IN_CLOEXEC: c_int = 0o2000000;
+const IN_MOVED_FROM: u32 = 0x40;
+const IN_MOVED_TO: u32 = 0x80;
+const IN_CREATE: u32 = 0x100;
+const IN_DELETE: u32 = 0x200;
+pub(crate) const POLLIN: c_short = 0x1;
+const POLLERR: c_short = 0x8;
+const POLLHUP: c_short = 0x10;
+const WATCH_MASK: u32 = IN_CREATE | IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO;
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: c_short,
+    revents: c_short,
+}
+
+extern "C" {
+    fn inotify_init1(flags: c_int) -> c_int;
+    fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+    #[link_name = "poll"]
+    fn poll_fds(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+}
+
+/// A change to a `discord-ipc-N` socket in one of the watched directories,
+/// or a missing socket directory being created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SocketEvent {
+    Created(String),
+    Removed(String),
+    /// A directory on the way to a missing socket directory was created,
+    /// such as the runtime directory of a flatpak started after the
+    /// watch. Watch again to see sockets created in it.
+    DirCreated(String),
+}
+
+/// Watches the socket directories with inotify, so that Discord starting or
+/// exiting is noticed without polling.
+pub(crate) struct SocketWatcher {
+    file: File,
+    dirs: Vec<(c_int, String)>,
+    /// Missing directories, along with the watch on the closest existing
+    /// directory above them.
+    awaited: Vec<(c_int, String)>,
+}
+
+impl SocketWatcher {
+    /// Watches every socket directory, or for a missing one, the closest
+    /// existing directory above it. Fails if nothing could be watched.
+    pub(crate) fn new() -> io::Result<Self> {
+        Self::watching(socket_dirs())
+    }
+
+    fn watching(dirs: impl Iterator<Item = String>) -> io::Result<Self> {
+        let fd = unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut watcher = Self {
+            file: unsafe { File::from_raw_fd(fd) },
+            dirs: Vec::new(),
+            awaited: Vec::new(),
+        };
+
+        for dir in dirs {
+            let mut missing = Path::new(&dir);
+            if missing.is_dir() {
+                watcher.watch(&dir);
+                continue;
+            }
+            while let Some(parent) = missing.parent() {
+                if !parent.is_dir() {
+                    missing = parent;
+                    continue;
+                }
+                if let (Some(wd), Some(missing)) = (
+                    parent.to_str().and_then(|parent| watcher.watch(parent)),
+                    missing.to_str(),
+                ) {
+                    watcher.awaited.push((wd, missing.to_string()));
+                }
+                break;
+            }
+        }
+
+        if watcher.dirs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No socket directory to watch",
+            ));
+        }
+
+        Ok(watcher)
+    }
+
+    /// Watches `dir` unless it already is, returning its watch descriptor.
+    fn watch(&mut self, dir: &str) -> Option<c_int> {
+        if let Some(&(wd, _)) =
+            self.dirs.iter().find(|(_, watched)| watched == dir)
+        {
+            return Some(wd);
+        }
+
+        let path = CString::new(dir).ok()?;
+        let wd = unsafe {
+            inotify_add_watch(self.file.as_raw_fd(), path.as_ptr(), WATCH_MASK)
+        };
+        if wd == -1 {
+            return None;
+        }
+        self.dirs.push((wd, dir.to_string()));
+
+        Some(wd)
+    }
+
+    /// Reads the pending events without blocking.
+    pub(crate) fn read_events(&mut self) -> io::Result<Vec<SocketEvent>> {
+        let mut events = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let len = match self.file.read(&mut buffer) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            };
+
+            let mut offset = 0;
+            while offset + 16 <= len {
+                let field = |at: usize| {
+                    u32::from_ne_bytes(
+                        buffer[offset + at..offset + at + 4]
+                            .try_into()
+                            .unwrap(),
+                    )
+                };
+                let (wd, mask, name_len) =
+                    (field(0) as c_int, field(4), field(12) as usize);
+                let name = &buffer[offset + 16..offset + 16 + name_len];
+                offset += 16 + name_len;
+
+                let name = String::from_utf8_lossy(name);
+                let name = name.trim_end_matches('\0');
+                let Some((_, dir)) = self.dirs.iter().find(|(w, _)| *w == wd)
+                else {
+                    continue;
+                };
+
+                let path = format!("{dir}/{name}");
+                let created = mask & (IN_CREATE | IN_MOVED_TO) != 0;
+                if name.starts_with("discord-ipc-") {
+                    events.push(if created {
+                        SocketEvent::Created(path)
+                    } else {
+                        SocketEvent::Removed(path)
+                    });
+                } else if created
+                    && self
+                        .awaited
+                        .iter()
+                        .any(|(w, awaited)| *w == wd && *awaited == path)
+                {
+                    events.push(SocketEvent::DirCreated(path));
+                }
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Shuts `pipe` down once the socket at `path` is removed, which wakes
+    /// up the thread reading from it. The watcher thread exits as soon as
+    /// the connection is closed for any reason.
+    pub(crate) fn watch_removal(mut self, path: String, pipe: UnixStream) {
+        thread::spawn(move || loop {
+            let Ok(revents) = poll(
+                &[(self.file.as_raw_fd(), POLLIN), (pipe.as_raw_fd(), 0)],
+                None,
+            ) else {
+                return;
+            };
+            if revents[1] & (POLLHUP | POLLERR) != 0 {
+                return;
+            }
+            if revents[0] == 0 {
+                continue;
+            }
+
+            match self.read_events() {
+                Ok(events)
+                    if events.contains(&SocketEvent::Removed(path.clone())) =>
+                {
+                    let _ = pipe.shutdown(std::net::Shutdown::Both);
+                    return;
+                }
+                Ok(_) => {}
+                Err(_) => return,
+            }
+        });
+    }
+}
+
+impl AsRawFd for SocketWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+/// Waits until one of `fds` is ready for the given events, or `timeout`
+/// elapses. Returns the events that occurred on each of them.
+pub(crate) fn poll(
+    fds: &[(RawFd, c_short)],
+    timeout: Option<Duration>,
+) -> io::Result<Vec<c_short>> {
+    let mut fds: Vec<PollFd> = fds
+        .iter()
+        .map(|&(fd, events)| PollFd {
+            fd,
+            events,
+            revents: 0,
+        })
+        .collect();
+    let timeout = timeout.map_or(-1, |timeout| {
+        timeout.as_millis().min(c_int::MAX as u128) as c_int
+    });
+
+    loop {
+        if unsafe { poll_fds(fds.as_mut_ptr(), fds.len() as c_ulong, timeout) }
+            != -1
+        {
+            return Ok(fds.iter().map(|fd| fd.revents).collect());
+        }
+
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn notices_missing_dirs_being_created() {
+        let base = std::env::temp_dir()
+            .join(format!("rpresence-inotify-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir(&base).unwrap();
+        let base = base.to_str().unwrap().to_string();
+        let app = format!("{base}/app");
+        let dir = format!("{app}/com.discordapp.Discord");
+
+        let mut watcher =
+            SocketWatcher::watching([base.clone(), dir.clone()].into_iter())
+                .unwrap();
+        fs::create_dir(format!("{base}/other")).unwrap();
+        fs::create_dir(&app).unwrap();
+        assert_eq!(
+            watcher.read_events().unwrap(),
+            [SocketEvent::DirCreated(app.clone())]
+        );
+
+        let mut watcher =
+            SocketWatcher::watching([base.clone(), dir.clone()].into_iter())
+                .unwrap();
+        fs::create_dir(&dir).unwrap();
+        assert_eq!(
+            watcher.read_events().unwrap(),
+            [SocketEvent::DirCreated(dir.clone())]
+        );
+
+        let mut watcher =
+            SocketWatcher::watching([base.clone(), dir.clone()].into_iter())
+                .unwrap();
+        let socket = format!("{dir}/discord-ipc-0");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket);
+        assert_eq!(
+            watcher.read_events().unwrap(),
+            [SocketEvent::Created(socket)]
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
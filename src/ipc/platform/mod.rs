@@ -2,4 +2,10 @@
 pub mod windows_connection;
 
 #[cfg(not(target_os = "windows"))]
-pub mod unix_connection;
\ No newline at end of file
+pub mod unix_connection;
+
+#[cfg(target_os = "linux")]
+pub mod inotify;
+
+#[cfg(not(target_os = "windows"))]
+pub mod unix_signal;
@@ -1,42 +1,292 @@
 use std::env::var;
-use std::io::{self, Read, Write};
+use std::ffi::{c_int, c_void};
+use std::fs;
+use std::io;
+#[cfg(target_os = "android")]
+use std::os::android::net::SocketAddrExt;
+use std::os::fd::AsRawFd;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::fs::MetadataExt;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::net::SocketAddr;
 use std::os::unix::net::UnixStream;
+use std::sync::RwLock;
 
-use crate::ipc::client::Connection;
-use crate::ipc::utils;
-use crate::rpc::packet::Packet;
+use crate::ipc::client::{Connection, Target};
+use crate::ipc::kind::ClientKind;
+use crate::ipc::policy::SocketPolicy;
+use crate::manager::discover;
 use crate::RichClient;
 
-impl Connection for RichClient<'_> {
-    fn open(&mut self) -> io::Result<()> {
-        let dirs = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
-            .iter()
-            .filter_map(|&dir| var(dir).ok())
-            .chain(["/tmp".to_string()])
-            .flat_map(|base| {
-                [
-                    base.to_string(),
-                    format!("{}/app/com.discordapp.Discord", base),
-                    format!("{}/snap.discord", base),
-                ]
-            });
-
-        for dir in dirs {
-            for i in 0..10 {
-                match UnixStream::connect(format!("{dir}/discord-ipc-{i}")) {
-                    Ok(pipe) => {
-                        self.pipe = Some(pipe).into();
-                        return Ok(());
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SOL_SOCKET: c_int = 1;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SO_PEERCRED: c_int = 17;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+struct UCred {
+    pid: i32,
+    uid: u32,
+    gid: u32,
+}
+
+extern "C" {
+    fn getuid() -> u32;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn getsockopt(
+        fd: c_int,
+        level: c_int,
+        name: c_int,
+        value: *mut c_void,
+        len: *mut u32,
+    ) -> c_int;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn getpeereid(fd: c_int, uid: *mut u32, gid: *mut u32) -> c_int;
+}
+
+/// Where each kind of client creates its sockets, relative to the runtime
+/// or temporary directory.
+const LOCATIONS: &[(ClientKind, &str)] = &[
+    (ClientKind::Discord, ""),
+    (ClientKind::Discord, "app/com.discordapp.Discord"),
+    (ClientKind::Canary, "app/com.discordapp.DiscordCanary"),
+    (ClientKind::Discord, "snap.discord"),
+    (ClientKind::Canary, "snap.discord-canary"),
+    (ClientKind::Vesktop, ".flatpak/dev.vencord.Vesktop/xdg-run"),
+];
+
+/// Clients that serve sockets in Discord's default location, identified by
+/// their command line.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const PROCESSES: &[(&str, ClientKind)] = &[
+    ("arrpc", ClientKind::ArRpc),
+    ("vesktop", ClientKind::Vesktop),
+    ("DiscordCanary", ClientKind::Canary),
+];
+
+fn socket_locations() -> impl Iterator<Item = (ClientKind, String)> {
+    ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .filter_map(|&dir| var(dir).ok())
+        .chain(["/tmp".to_string()])
+        .flat_map(|base| {
+            LOCATIONS.iter().map(move |&(kind, dir)| match dir {
+                "" => (kind, base.clone()),
+                _ => (kind, format!("{base}/{dir}")),
+            })
+        })
+}
+
+/// The directories clients may create their sockets in.
+#[cfg(target_os = "linux")]
+pub(crate) fn socket_dirs() -> impl Iterator<Item = String> {
+    socket_locations().map(|(_, dir)| dir)
+}
+
+/// Every socket to try, in order. Abstract sockets are named with a
+/// leading `@`.
+fn candidates() -> impl Iterator<Item = (ClientKind, String)> {
+    let paths = socket_locations().flat_map(|(kind, dir)| {
+        (0..10).map(move |i| (kind, format!("{dir}/discord-ipc-{i}")))
+    });
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let paths = paths.chain(
+        (0..10).map(|i| (ClientKind::Discord, format!("@discord-ipc-{i}"))),
+    );
+
+    paths
+}
+
+fn connect(path: &str) -> io::Result<UnixStream> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(name) = path.strip_prefix('@') {
+        return UnixStream::connect_addr(&SocketAddr::from_abstract_name(
+            name,
+        )?);
+    }
+
+    UnixStream::connect(path)
+}
+
+/// Connects to the first socket found that `policy` allows, returning it
+/// along with the kind of client serving it, its path and the sockets
+/// skipped by `policy`. If none is found and some were skipped, the error
+/// says which and why.
+///
+/// Abstract sockets have no file, so only their peer is checked.
+pub(crate) fn open_pipe(
+    policy: SocketPolicy,
+) -> io::Result<(UnixStream, Target)> {
+    let mut skipped = Vec::new();
+    for (kind, path) in candidates() {
+        if policy == SocketPolicy::SameUser && !path.starts_with('@') {
+            match check_owner(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => {
+                    skipped.push(format!("{path}: {e}"));
+                    continue;
+                }
+            }
+        }
+
+        match connect(&path) {
+            Ok(pipe) => {
+                if policy == SocketPolicy::SameUser {
+                    if let Err(e) = check_peer(&pipe) {
+                        skipped.push(format!("{path}: {e}"));
+                        continue;
                     }
-                    Err(e) => match e.kind() {
-                        io::ErrorKind::NotFound => continue,
-                        _ => return Err(e),
-                    },
                 }
+                let kind = identify(&pipe, kind);
+                return Ok((
+                    pipe,
+                    Target {
+                        kind,
+                        path,
+                        skipped,
+                    },
+                ));
             }
+            Err(e) => match e.kind() {
+                // A stale socket left behind by a client that crashed
+                // refuses connections.
+                io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => {
+                    continue
+                }
+                _ => return Err(e),
+            },
         }
+    }
+
+    if !skipped.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("No allowed pipe found, skipped {}", skipped.join("; ")),
+        ));
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found"))
+}
 
-        Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found"))
+/// Connects to the socket at `path` if `policy` allows it.
+pub(crate) fn open_path(
+    path: &str,
+    policy: SocketPolicy,
+) -> io::Result<UnixStream> {
+    if policy == SocketPolicy::SameUser && !path.starts_with('@') {
+        check_owner(path)?;
+    }
+    let pipe = connect(path)?;
+    if policy == SocketPolicy::SameUser {
+        check_peer(&pipe)?;
+    }
+
+    Ok(pipe)
+}
+
+/// Tells apart the clients sharing Discord's default socket location by
+/// the command line of the process serving `pipe`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn identify(pipe: &UnixStream, kind: ClientKind) -> ClientKind {
+    if kind != ClientKind::Discord {
+        return kind;
+    }
+    let Ok(cmdline) = peer_cred(pipe)
+        .and_then(|cred| fs::read(format!("/proc/{}/cmdline", cred.pid)))
+    else {
+        return kind;
+    };
+
+    let cmdline = String::from_utf8_lossy(&cmdline).to_lowercase();
+    PROCESSES
+        .iter()
+        .find(|(name, _)| cmdline.contains(&name.to_lowercase()))
+        .map_or(kind, |&(_, kind)| kind)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn identify(_pipe: &UnixStream, kind: ClientKind) -> ClientKind {
+    kind
+}
+
+/// Fails unless the socket file at `path` is owned by the current user.
+fn check_owner(path: &str) -> io::Result<()> {
+    let uid = unsafe { getuid() };
+    let owner = fs::symlink_metadata(path)?.uid();
+    if owner != uid {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("socket is owned by uid {owner}, not {uid}"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fails unless the process serving `pipe` runs as the current user.
+fn check_peer(pipe: &UnixStream) -> io::Result<()> {
+    let uid = unsafe { getuid() };
+    let peer = peer_uid(pipe)?;
+    if peer != uid {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("peer runs as uid {peer}, not {uid}"),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(pipe: &UnixStream) -> io::Result<u32> {
+    peer_cred(pipe).map(|cred| cred.uid)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_cred(pipe: &UnixStream) -> io::Result<UCred> {
+    let mut cred = UCred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<UCred>() as u32;
+    if unsafe {
+        getsockopt(
+            pipe.as_raw_fd(),
+            SOL_SOCKET,
+            SO_PEERCRED,
+            &mut cred as *mut UCred as *mut c_void,
+            &mut len,
+        )
+    } == -1
+    {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(cred)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(pipe: &UnixStream) -> io::Result<u32> {
+    let (mut uid, mut gid) = (0, 0);
+    if unsafe { getpeereid(pipe.as_raw_fd(), &mut uid, &mut gid) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(uid)
+}
+
+impl Connection for RichClient<'_> {
+    fn open(&mut self) -> io::Result<()> {
+        let (pipe, target) =
+            discover(self.socket_policy, self.discovery.as_deref())?;
+        *self.pipe.write().unwrap() = Some(pipe);
+        *self.target.write().unwrap() = Some(target);
+
+        Ok(())
     }
 
     fn close(&mut self) -> io::Result<()> {
@@ -47,7 +297,7 @@ impl Connection for RichClient<'_> {
                     .as_bytes(),
             ),
         )?;
-        if let Some(pipe) = self.pipe.as_ref().as_ref().as_mut().take() {
+        if let Some(pipe) = self.pipe.read().unwrap().as_ref() {
             let _ = pipe.shutdown(std::net::Shutdown::Both);
         }
 
@@ -55,7 +305,7 @@ impl Connection for RichClient<'_> {
     }
 
     fn _close(
-        pipe: &Option<std::os::unix::net::UnixStream>,
+        pipe: &RwLock<Option<UnixStream>>,
         client_id: u64,
     ) -> io::Result<()> {
         RichClient::_write(
@@ -63,7 +313,7 @@ impl Connection for RichClient<'_> {
             2,
             Some(format!("{{'v': 1, 'client_id': {}}}", client_id).as_bytes()),
         )?;
-        if let Some(pipe) = pipe.as_ref().as_ref().as_mut().take() {
+        if let Some(pipe) = pipe.read().unwrap().as_ref() {
             let _ = pipe.shutdown(std::net::Shutdown::Both);
         }
 
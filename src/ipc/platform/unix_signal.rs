@@ -0,0 +1,129 @@
+use std::ffi::{c_int, c_void};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::FromRawFd;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, RwLock, Weak};
+use std::thread;
+
+use crate::ipc::client::Connection;
+use crate::rpc::command::Command;
+use crate::RichClient;
+
+const SIGHUP: c_int = 1;
+const SIGINT: c_int = 2;
+const SIGTERM: c_int = 15;
+const SIG_DFL: usize = 0;
+const SIG_ERR: usize = !0;
+
+extern "C" {
+    fn pipe(fds: *mut c_int) -> c_int;
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+    fn signal(signum: c_int, handler: usize) -> usize;
+    fn raise(signum: c_int) -> c_int;
+}
+
+struct Registration {
+    pipe: Weak<RwLock<Option<UnixStream>>>,
+    pid: u32,
+    client_id: u64,
+}
+
+static CLIENTS: Mutex<Vec<Registration>> = Mutex::new(Vec::new());
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Keeps track of a connected client so that its presence can be cleared
+/// from the signal handler.
+pub(crate) fn register(
+    pipe: Weak<RwLock<Option<UnixStream>>>,
+    pid: u32,
+    client_id: u64,
+) {
+    let mut clients = CLIENTS.lock().unwrap();
+    clients.retain(|client| client.pipe.strong_count() > 0);
+    clients.push(Registration {
+        pipe,
+        pid,
+        client_id,
+    });
+}
+
+/// Installs handlers for `SIGINT`, `SIGTERM` and `SIGHUP` that clear the
+/// presence of every live client and send the close frame before the
+/// process exits.
+///
+/// The handler itself only writes to a pipe; the actual cleanup runs on a
+/// dedicated thread, after which the signal is re-raised with its default
+/// disposition. Calling this more than once has no effect.
+pub fn install_signal_handlers() -> io::Result<()> {
+    if WRITE_FD.load(Ordering::SeqCst) != -1 {
+        return Ok(());
+    }
+
+    let mut fds = [0; 2];
+    if unsafe { pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if WRITE_FD
+        .compare_exchange(-1, fds[1], Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        unsafe {
+            drop(File::from_raw_fd(fds[0]));
+            drop(File::from_raw_fd(fds[1]));
+        }
+        return Ok(());
+    }
+
+    let mut reader = unsafe { File::from_raw_fd(fds[0]) };
+    thread::spawn(move || {
+        let mut signum = [0u8; 1];
+        if reader.read_exact(&mut signum).is_err() {
+            return;
+        }
+
+        for client in CLIENTS.lock().unwrap().iter() {
+            if let Some(pipe) = client.pipe.upgrade() {
+                let _ = RichClient::_write(
+                    &pipe,
+                    1,
+                    Some(
+                        Command::SetActivity {
+                            pid: client.pid,
+                            activity: None,
+                        }
+                        .to_json("-")
+                        .unwrap()
+                        .as_bytes(),
+                    ),
+                );
+                let _ = RichClient::_close(&pipe, client.client_id);
+            }
+        }
+
+        unsafe {
+            signal(signum[0] as c_int, SIG_DFL);
+            raise(signum[0] as c_int);
+        }
+    });
+
+    for signum in [SIGINT, SIGTERM, SIGHUP] {
+        if unsafe {
+            signal(signum, handle_signal as extern "C" fn(c_int) as usize)
+        } == SIG_ERR
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+extern "C" fn handle_signal(signum: c_int) {
+    let fd = WRITE_FD.load(Ordering::Relaxed);
+    let byte = signum as u8;
+    unsafe {
+        write(fd, &byte as *const u8 as *const c_void, 1);
+    }
+}
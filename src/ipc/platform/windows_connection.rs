@@ -1,35 +1,60 @@
 use std::ffi::c_void;
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
 use std::io;
 use std::os::windows::io::AsRawHandle;
+use std::sync::RwLock;
 
-use crate::ipc::client::Connection;
+use crate::ipc::client::{Connection, Target};
+use crate::ipc::kind::ClientKind;
+use crate::ipc::policy::SocketPolicy;
+use crate::manager::discover;
 use crate::RichClient;
 
 extern "system" {
     fn CloseHandle(hObject: *mut c_void) -> i32;
 }
 
-impl Connection for RichClient<'_> {
-    fn open(&mut self) -> io::Result<()> {
-        for i in 0..10 {
-            match OpenOptions::new()
-                .read(true)
-                .write(true)
-                .open(format!("\\\\.\\pipe\\discord-ipc-{i}"))
-            {
-                Ok(pipe) => {
-                    self.pipe = Some(pipe).into();
-                    return Ok(());
-                }
-                Err(e) => match e.kind() {
-                    io::ErrorKind::NotFound => continue,
-                    _ => return Err(e),
-                },
+/// Connects to the first Discord pipe found, returning it along with the
+/// kind of client serving it and its path. Named pipes are private to the
+/// session, so `policy` is ignored.
+pub(crate) fn open_pipe(policy: SocketPolicy) -> io::Result<(File, Target)> {
+    for i in 0..10 {
+        let path = format!("\\\\.\\pipe\\discord-ipc-{i}");
+        match open_path(&path, policy) {
+            Ok(pipe) => {
+                return Ok((
+                    pipe,
+                    Target {
+                        kind: ClientKind::Discord,
+                        path,
+                        skipped: Vec::new(),
+                    },
+                ))
             }
+            Err(e) => match e.kind() {
+                io::ErrorKind::NotFound => continue,
+                _ => return Err(e),
+            },
         }
+    }
 
-        Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found"))
+    Err(io::Error::new(io::ErrorKind::NotFound, "Pipe not found"))
+}
+
+/// Connects to the pipe at `path`. Named pipes are private to the session,
+/// so `policy` is ignored.
+pub(crate) fn open_path(path: &str, _policy: SocketPolicy) -> io::Result<File> {
+    OpenOptions::new().read(true).write(true).open(path)
+}
+
+impl Connection for RichClient<'_> {
+    fn open(&mut self) -> io::Result<()> {
+        let (pipe, target) =
+            discover(self.socket_policy, self.discovery.as_deref())?;
+        *self.pipe.write().unwrap() = Some(pipe);
+        *self.target.write().unwrap() = Some(target);
+
+        Ok(())
     }
 
     fn close(&mut self) -> io::Result<()> {
@@ -40,7 +65,7 @@ impl Connection for RichClient<'_> {
                     .as_bytes(),
             ),
         )?;
-        if let Some(pipe) = self.pipe.as_ref().as_ref().as_mut().take() {
+        if let Some(pipe) = self.pipe.read().unwrap().as_ref() {
             unsafe {
                 CloseHandle(pipe.as_raw_handle());
             }
@@ -49,13 +74,16 @@ impl Connection for RichClient<'_> {
         Ok(())
     }
 
-    fn _close(pipe: &Option<std::fs::File>, client_id: u64) -> io::Result<()> {
+    fn _close(
+        pipe: &RwLock<Option<std::fs::File>>,
+        client_id: u64,
+    ) -> io::Result<()> {
         RichClient::_write(
             pipe,
             2,
             Some(format!("{{'v': 1, 'client_id': {}}}", client_id).as_bytes()),
         )?;
-        if let Some(pipe) = pipe.as_ref().as_mut().take() {
+        if let Some(pipe) = pipe.read().unwrap().as_ref() {
             unsafe {
                 CloseHandle(pipe.as_raw_handle());
             }
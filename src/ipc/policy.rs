@@ -0,0 +1,19 @@
+/// Which discovered sockets the client is willing to connect to.
+///
+/// Some of the directories searched for Discord's socket, such as `/tmp`,
+/// are writable by every user, so on a shared machine another user could
+/// create a fake socket there and receive the presence, including join
+/// secrets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SocketPolicy {
+    /// Connect to the first socket found.
+    #[default]
+    Any,
+    /// Skip sockets whose file is not owned by the current user, and
+    /// sockets whose peer does not run as the current user. If no other
+    /// socket is found, connecting fails with
+    /// [`PermissionDenied`](std::io::ErrorKind::PermissionDenied), naming
+    /// the sockets skipped and why. Has no effect on Windows, where named
+    /// pipes are already private to the session.
+    SameUser,
+}
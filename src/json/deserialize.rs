@@ -0,0 +1,408 @@
+use std::io;
+
+use crate::rpc::ack::AckedActivity;
+use crate::rpc::activity::{ActivityFlags, ActivityType};
+use crate::rpc::auth::{Application, AuthCode, AuthenticatedSession};
+use crate::rpc::command::{Command, Response};
+use crate::rpc::event::Dispatch;
+use crate::rpc::guild::{Channel, ChannelType, Guild};
+use crate::rpc::timestamp::Timestamp;
+use crate::rpc::user::User;
+use crate::rpc::voice::{
+    AudioDevice, VoiceConnectionStatus, VoiceIo, VoiceMode, VoiceSettings,
+    VoiceState,
+};
+
+use super::Value;
+
+/// Returns the string stored under `key`, or an error naming the missing
+/// field.
+pub(crate) fn str_field<'v>(
+    value: &'v Value,
+    key: &str,
+) -> io::Result<&'v str> {
+    value.get(key).and_then(Value::as_str).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Missing field `{key}`"),
+        )
+    })
+}
+
+/// Returns the string stored under `key`, if present and not null.
+pub(crate) fn opt_str_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+/// Returns the strings in the array stored under `key`.
+pub(crate) fn str_array_field(value: &Value, key: &str) -> Vec<String> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the boolean stored under `key`, treating a missing field as
+/// `false`.
+pub(crate) fn bool_field(value: &Value, key: &str) -> bool {
+    value.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Parses each element of the array stored under `key` with `parse`.
+fn array_field<T>(
+    value: &Value,
+    key: &str,
+    parse: impl Fn(&Value) -> io::Result<T>,
+) -> io::Result<Vec<T>> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .map(parse)
+        .collect()
+}
+
+/// Returns `None` for a missing or null value.
+fn non_null(value: Option<&Value>) -> Option<&Value> {
+    value.filter(|value| !value.is_null())
+}
+
+impl User {
+    pub(crate) fn from_value(value: &Value) -> io::Result<Self> {
+        Ok(Self {
+            id: str_field(value, "id")?.to_string(),
+            username: str_field(value, "username")?.to_string(),
+            discriminator: opt_str_field(value, "discriminator"),
+            global_name: opt_str_field(value, "global_name"),
+            avatar: opt_str_field(value, "avatar"),
+            bot: bool_field(value, "bot"),
+        })
+    }
+}
+
+impl Application {
+    pub(crate) fn from_value(value: &Value) -> io::Result<Self> {
+        Ok(Self {
+            id: str_field(value, "id")?.to_string(),
+            name: str_field(value, "name")?.to_string(),
+            description: opt_str_field(value, "description"),
+            icon: opt_str_field(value, "icon"),
+            rpc_origins: str_array_field(value, "rpc_origins"),
+        })
+    }
+}
+
+impl AuthenticatedSession {
+    pub(crate) fn from_value(value: &Value) -> io::Result<Self> {
+        Ok(Self {
+            application: Application::from_value(
+                value.get("application").unwrap_or(&Value::Null),
+            )?,
+            user: User::from_value(value.get("user").unwrap_or(&Value::Null))?,
+            scopes: str_array_field(value, "scopes"),
+            expires: opt_str_field(value, "expires").unwrap_or_default(),
+        })
+    }
+}
+
+impl VoiceSettings {
+    pub(crate) fn from_value(value: &Value) -> io::Result<Self> {
+        let flag = |key| value.get(key).and_then(Value::as_bool);
+
+        Ok(Self {
+            input: non_null(value.get("input")).map(VoiceIo::from_value),
+            output: non_null(value.get("output")).map(VoiceIo::from_value),
+            mode: non_null(value.get("mode")).map(VoiceMode::from_value),
+            automatic_gain_control: flag("automatic_gain_control"),
+            echo_cancellation: flag("echo_cancellation"),
+            noise_suppression: flag("noise_suppression"),
+            qos: flag("qos"),
+            silence_warning: flag("silence_warning"),
+            deaf: flag("deaf"),
+            mute: flag("mute"),
+        })
+    }
+}
+
+impl VoiceIo {
+    fn from_value(value: &Value) -> Self {
+        Self {
+            device_id: opt_str_field(value, "device_id"),
+            volume: value.get("volume").and_then(Value::as_f64),
+            available_devices: value
+                .get("available_devices")
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|device| {
+                    Some(AudioDevice {
+                        id: opt_str_field(device, "id")?,
+                        name: opt_str_field(device, "name")?,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl VoiceMode {
+    fn from_value(value: &Value) -> Self {
+        Self {
+            ty: opt_str_field(value, "type"),
+            auto_threshold: value
+                .get("auto_threshold")
+                .and_then(Value::as_bool),
+            threshold: value.get("threshold").and_then(Value::as_f64),
+            delay: value.get("delay").and_then(Value::as_f64),
+        }
+    }
+}
+
+impl Guild {
+    pub(crate) fn from_value(value: &Value) -> io::Result<Self> {
+        Ok(Self {
+            id: str_field(value, "id")?.to_string(),
+            name: opt_str_field(value, "name").unwrap_or_default(),
+            icon_url: opt_str_field(value, "icon_url"),
+            ty: value.get("type").and_then(Value::as_u64),
+            voice_states: non_null(value.get("voice_states"))
+                .map(|_| {
+                    array_field(value, "voice_states", VoiceState::from_value)
+                })
+                .transpose()?,
+        })
+    }
+}
+
+impl Channel {
+    pub(crate) fn from_value(value: &Value) -> io::Result<Self> {
+        Ok(Self {
+            id: str_field(value, "id")?.to_string(),
+            name: opt_str_field(value, "name").unwrap_or_default(),
+            ty: ChannelType::from_u64(
+                value
+                    .get("type")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default(),
+            ),
+            guild_id: opt_str_field(value, "guild_id"),
+            topic: opt_str_field(value, "topic"),
+            bitrate: value.get("bitrate").and_then(Value::as_u64),
+            user_limit: value.get("user_limit").and_then(Value::as_u64),
+            position: value.get("position").and_then(Value::as_u64),
+            voice_states: array_field(
+                value,
+                "voice_states",
+                VoiceState::from_value,
+            )?,
+        })
+    }
+
+    /// Parses a channel that Discord reports as `null` when there is none.
+    fn from_nullable(value: &Value) -> io::Result<Option<Self>> {
+        non_null(Some(value)).map(Self::from_value).transpose()
+    }
+}
+
+impl VoiceState {
+    pub(crate) fn from_value(value: &Value) -> io::Result<Self> {
+        let state = value.get("voice_state").unwrap_or(&Value::Null);
+
+        Ok(Self {
+            user: User::from_value(value.get("user").unwrap_or(&Value::Null))?,
+            nick: opt_str_field(value, "nick"),
+            volume: value.get("volume").and_then(Value::as_f64),
+            muted_locally: bool_field(value, "mute"),
+            mute: bool_field(state, "mute"),
+            deaf: bool_field(state, "deaf"),
+            self_mute: bool_field(state, "self_mute"),
+            self_deaf: bool_field(state, "self_deaf"),
+            suppress: bool_field(state, "suppress"),
+        })
+    }
+}
+
+impl VoiceConnectionStatus {
+    fn from_value(value: &Value) -> io::Result<Self> {
+        Ok(Self {
+            state: str_field(value, "state")?.to_string(),
+            hostname: opt_str_field(value, "hostname"),
+            average_ping: value.get("average_ping").and_then(Value::as_f64),
+            last_ping: value.get("last_ping").and_then(Value::as_f64),
+        })
+    }
+}
+
+impl Dispatch {
+    pub(crate) fn from_value(evt: &str, data: &Value) -> io::Result<Self> {
+        Ok(match evt {
+            "VOICE_CHANNEL_SELECT" => Dispatch::VoiceChannelSelect {
+                channel_id: opt_str_field(data, "channel_id"),
+                guild_id: opt_str_field(data, "guild_id"),
+            },
+            "VOICE_SETTINGS_UPDATE" => {
+                Dispatch::VoiceSettingsUpdate(VoiceSettings::from_value(data)?)
+            }
+            "VOICE_STATE_CREATE" => {
+                Dispatch::VoiceStateCreate(VoiceState::from_value(data)?)
+            }
+            "VOICE_STATE_UPDATE" => {
+                Dispatch::VoiceStateUpdate(VoiceState::from_value(data)?)
+            }
+            "VOICE_STATE_DELETE" => {
+                Dispatch::VoiceStateDelete(VoiceState::from_value(data)?)
+            }
+            "SPEAKING_START" => Dispatch::SpeakingStart {
+                user_id: str_field(data, "user_id")?.to_string(),
+            },
+            "SPEAKING_STOP" => Dispatch::SpeakingStop {
+                user_id: str_field(data, "user_id")?.to_string(),
+            },
+            "VOICE_CONNECTION_STATUS" => Dispatch::VoiceConnectionStatus(
+                VoiceConnectionStatus::from_value(data)?,
+            ),
+            "GUILD_STATUS" => Dispatch::GuildStatus {
+                guild: Guild::from_value(
+                    data.get("guild").unwrap_or(&Value::Null),
+                )?,
+                online: data.get("online").and_then(Value::as_u64),
+            },
+            "CHANNEL_CREATE" => {
+                Dispatch::ChannelCreate(Channel::from_value(data)?)
+            }
+            _ => Dispatch::Other {
+                evt: evt.to_string(),
+                data: data.clone(),
+            },
+        })
+    }
+}
+
+impl AckedActivity {
+    pub(crate) fn from_value(value: &Value) -> Self {
+        let timestamps = value.get("timestamps").unwrap_or(&Value::Null);
+        let assets = value.get("assets").unwrap_or(&Value::Null);
+        let party = value.get("party").unwrap_or(&Value::Null);
+        let timestamp = |key| {
+            timestamps
+                .get(key)
+                .and_then(Value::as_u64)
+                .map(Timestamp::from_millis)
+        };
+
+        Self {
+            application_id: opt_str_field(value, "application_id"),
+            name: opt_str_field(value, "name"),
+            ty: value.get("type").and_then(Value::as_u64).and_then(
+                |ty| match ty {
+                    0 => Some(ActivityType::Playing),
+                    2 => Some(ActivityType::Listening),
+                    3 => Some(ActivityType::Watching),
+                    5 => Some(ActivityType::Competing),
+                    _ => None,
+                },
+            ),
+            details: opt_str_field(value, "details"),
+            details_url: opt_str_field(value, "details_url"),
+            state: opt_str_field(value, "state"),
+            state_url: opt_str_field(value, "state_url"),
+            start: timestamp("start"),
+            end: timestamp("end"),
+            large_image: opt_str_field(assets, "large_image"),
+            large_text: opt_str_field(assets, "large_text"),
+            large_url: opt_str_field(assets, "large_url"),
+            small_image: opt_str_field(assets, "small_image"),
+            small_text: opt_str_field(assets, "small_text"),
+            small_url: opt_str_field(assets, "small_url"),
+            party_id: opt_str_field(party, "id"),
+            party_size: party.get("size").and_then(Value::as_array).and_then(
+                |size| Some([size.first()?.as_u64()?, size.get(1)?.as_u64()?]),
+            ),
+            buttons: value
+                .get("buttons")
+                .and_then(Value::as_array)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|button| {
+                    button
+                        .as_str()
+                        .or_else(|| button.get("label").and_then(Value::as_str))
+                })
+                .map(str::to_string)
+                .collect(),
+            instance: value.get("instance").and_then(Value::as_bool),
+            flags: value
+                .get("flags")
+                .and_then(Value::as_u64)
+                .map(|flags| ActivityFlags::from_bits(flags as u32)),
+        }
+    }
+}
+
+impl Response {
+    /// Interprets the `data` of a response to `command`.
+    pub(crate) fn from_data(
+        command: &Command,
+        data: Value,
+    ) -> io::Result<Self> {
+        Ok(match command {
+            Command::SetActivity { .. } => Response::SetActivity(
+                non_null(Some(&data))
+                    .map(|data| Box::new(AckedActivity::from_value(data))),
+            ),
+            Command::Subscribe(_) => Response::Subscribe {
+                evt: str_field(&data, "evt")?.to_string(),
+            },
+            Command::Unsubscribe(_) => Response::Unsubscribe {
+                evt: str_field(&data, "evt")?.to_string(),
+            },
+            Command::Authorize { .. } => Response::Authorize(AuthCode {
+                code: str_field(&data, "code")?.to_string(),
+            }),
+            Command::Authenticate { .. } => Response::Authenticate(Box::new(
+                AuthenticatedSession::from_value(&data)?,
+            )),
+            Command::GetGuild { .. } => {
+                Response::GetGuild(Guild::from_value(&data)?)
+            }
+            Command::GetGuilds => Response::GetGuilds(array_field(
+                &data,
+                "guilds",
+                Guild::from_value,
+            )?),
+            Command::GetChannel { .. } => {
+                Response::GetChannel(Channel::from_value(&data)?)
+            }
+            Command::GetChannels { .. } => Response::GetChannels(array_field(
+                &data,
+                "channels",
+                Channel::from_value,
+            )?),
+            Command::SelectVoiceChannel { .. } => {
+                Response::SelectVoiceChannel(Channel::from_nullable(&data)?)
+            }
+            Command::GetSelectedVoiceChannel => {
+                Response::GetSelectedVoiceChannel(Channel::from_nullable(
+                    &data,
+                )?)
+            }
+            Command::GetVoiceSettings => {
+                Response::GetVoiceSettings(VoiceSettings::from_value(&data)?)
+            }
+            Command::SetVoiceSettings(_) => {
+                Response::SetVoiceSettings(VoiceSettings::from_value(&data)?)
+            }
+            Command::SendActivityJoinInvite { .. } => {
+                Response::SendActivityJoinInvite
+            }
+            Command::CloseActivityRequest { .. } => {
+                Response::CloseActivityRequest
+            }
+        })
+    }
+}
@@ -1,2 +1,6 @@
+mod deserialize;
 mod serialize;
-mod utils;
\ No newline at end of file
+mod utils;
+mod value;
+
+pub use value::{ParseError, Value};
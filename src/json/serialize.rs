@@ -1,44 +1,140 @@
 use crate::rpc::activity::Activity;
-use crate::rpc::packet::Packet;
+use crate::rpc::command::Command;
+use crate::rpc::event::Event;
+use crate::rpc::voice::{VoiceIo, VoiceMode, VoiceSettings};
 
 use std::fmt::{Error, Write};
 
 use super::utils::escape_json;
+use super::Value;
 
-impl Packet<'_> {
-    pub fn to_json(&self) -> Result<String, Error> {
+impl Command<'_> {
+    pub fn to_json(&self, nonce: &str) -> Result<String, Error> {
         let mut json_str = String::new();
 
-        json_str.push_str("{\"cmd\":\"SET_ACTIVITY\"");
-        json_str.push_str(",\"nonce\":\"-\"");
-        json_str.push_str(",\"args\":{");
+        write!(json_str, "{{\"cmd\":\"{}\"", self.name())?;
+        write!(json_str, ",\"nonce\":\"{}\"", escape_json(nonce))?;
 
-        write!(&mut json_str, "\"pid\":{}", self.pid)?;
-        if let Some(activity) = &self.activity {
-            json_str.push_str(",\"activity\":");
-            activity.push_json(&mut json_str)?;
+        if let Command::Subscribe(event) | Command::Unsubscribe(event) = self {
+            write!(json_str, ",\"evt\":\"{}\"", event.name())?;
         }
 
-        json_str.push_str("}}");
+        json_str.push_str(",\"args\":");
+
+        if let Command::SetActivity { pid, activity } = self {
+            write!(json_str, "{{\"pid\":{}", pid)?;
+            if let Some(activity) = activity {
+                json_str.push_str(",\"activity\":");
+                activity.push_json(&mut json_str)?;
+            }
+            json_str.push('}');
+        } else {
+            self.args().push_json(&mut json_str)?;
+        }
+
+        json_str.push('}');
 
         Ok(json_str)
     }
+
+    fn args(&self) -> Value {
+        let mut args: Vec<(&str, Value)> = Vec::new();
+
+        match self {
+            Command::SetActivity { pid, .. } => {
+                args.push(("pid", u64::from(*pid).into()));
+            }
+            Command::Subscribe(event) | Command::Unsubscribe(event) => {
+                return event.args();
+            }
+            Command::Authorize {
+                client_id,
+                scopes,
+                rpc_token,
+                username,
+            } => {
+                args.push(("client_id", client_id.to_string().into()));
+                args.push((
+                    "scopes",
+                    scopes
+                        .iter()
+                        .map(|scope| scope.as_str().into())
+                        .collect::<Vec<_>>()
+                        .into(),
+                ));
+                if let Some(rpc_token) = rpc_token {
+                    args.push(("rpc_token", (*rpc_token).into()));
+                }
+                if let Some(username) = username {
+                    args.push(("username", (*username).into()));
+                }
+            }
+            Command::Authenticate { access_token } => {
+                args.push(("access_token", (*access_token).into()));
+            }
+            Command::GetGuild { guild_id, timeout } => {
+                args.push(("guild_id", (*guild_id).into()));
+                if let Some(timeout) = timeout {
+                    args.push(("timeout", u64::from(*timeout).into()));
+                }
+            }
+            Command::GetGuilds
+            | Command::GetSelectedVoiceChannel
+            | Command::GetVoiceSettings => {}
+            Command::GetChannel { channel_id } => {
+                args.push(("channel_id", (*channel_id).into()));
+            }
+            Command::GetChannels { guild_id } => {
+                args.push(("guild_id", (*guild_id).into()));
+            }
+            Command::SelectVoiceChannel {
+                channel_id,
+                timeout,
+                force,
+            } => {
+                args.push(("channel_id", (*channel_id).into()));
+                if let Some(timeout) = timeout {
+                    args.push(("timeout", u64::from(*timeout).into()));
+                }
+                args.push(("force", (*force).into()));
+            }
+            Command::SetVoiceSettings(settings) => return settings.to_value(),
+            Command::SendActivityJoinInvite { user_id }
+            | Command::CloseActivityRequest { user_id } => {
+                args.push(("user_id", (*user_id).into()));
+            }
+        }
+
+        Value::object(args)
+    }
 }
 
 impl Activity<'_> {
     pub fn push_json(&self, json_str: &mut String) -> Result<(), Error> {
         write!(json_str, "{{\"type\":{}", self.ty.to_u8())?;
 
+        if let Some(name) = &self.name {
+            write!(json_str, ",\"name\":\"{}\"", escape_json(name))?;
+        }
+
+        if let Some(status_display_type) = &self.status_display_type {
+            write!(
+                json_str,
+                ",\"status_display_type\":{}",
+                status_display_type.to_u8()
+            )?;
+        }
+
         if let Some(timestamps) = &self.timestamps {
             if timestamps.start.is_some() || timestamps.end.is_some() {
                 json_str.push_str(",\"timestamps\":{");
 
                 if let Some(start) = timestamps.start {
-                    write!(json_str, "\"start\":{},", start)?;
+                    write!(json_str, "\"start\":{},", start.as_millis())?;
                 }
 
                 if let Some(end) = timestamps.end {
-                    write!(json_str, "\"end\":{}", end)?;
+                    write!(json_str, "\"end\":{}", end.as_millis())?;
                 }
 
                 if json_str.ends_with(',') {
@@ -53,10 +149,22 @@ impl Activity<'_> {
             write!(json_str, ",\"details\":\"{}\"", escape_json(details))?;
         }
 
+        if let Some(details_url) = &self.details_url {
+            write!(
+                json_str,
+                ",\"details_url\":\"{}\"",
+                escape_json(details_url)
+            )?;
+        }
+
         if let Some(state) = &self.state {
             write!(json_str, ",\"state\":\"{}\"", escape_json(state))?;
         }
 
+        if let Some(state_url) = &self.state_url {
+            write!(json_str, ",\"state_url\":\"{}\"", escape_json(state_url))?;
+        }
+
         if let Some(party) = &self.party {
             json_str.push_str(",\"party\":{");
 
@@ -112,6 +220,14 @@ impl Activity<'_> {
                 )?;
             }
 
+            if let Some(large_url) = &assets.large_url {
+                write!(
+                    json_str,
+                    "\"large_url\":\"{}\",",
+                    escape_json(large_url)
+                )?;
+            }
+
             if let Some(small_image) = &assets.small_image {
                 write!(json_str, "\"small_image\":\"{}\",", small_image)?;
             }
@@ -119,11 +235,19 @@ impl Activity<'_> {
             if let Some(small_text) = &assets.small_text {
                 write!(
                     json_str,
-                    "\"small_text\":\"{}\"",
+                    "\"small_text\":\"{}\",",
                     escape_json(small_text)
                 )?;
             }
 
+            if let Some(small_url) = &assets.small_url {
+                write!(
+                    json_str,
+                    "\"small_url\":\"{}\"",
+                    escape_json(small_url)
+                )?;
+            }
+
             if json_str.ends_with(',') {
                 json_str.pop();
             }
@@ -149,8 +273,156 @@ impl Activity<'_> {
             json_str.push(']');
         }
 
+        if let Some(instance) = self.instance {
+            write!(json_str, ",\"instance\":{}", instance)?;
+        }
+
+        if let Some(flags) = &self.flags {
+            write!(json_str, ",\"flags\":{}", flags.bits())?;
+        }
+
         json_str.push('}');
 
         Ok(())
     }
 }
+
+impl Event<'_> {
+    fn args(&self) -> Value {
+        match self {
+            Event::VoiceStateCreate { channel_id }
+            | Event::VoiceStateUpdate { channel_id }
+            | Event::VoiceStateDelete { channel_id }
+            | Event::SpeakingStart { channel_id }
+            | Event::SpeakingStop { channel_id } => {
+                Value::object([("channel_id", (*channel_id).into())])
+            }
+            Event::GuildStatus { guild_id } => {
+                Value::object([("guild_id", (*guild_id).into())])
+            }
+            _ => Value::object::<&str>([]),
+        }
+    }
+}
+
+impl VoiceSettings {
+    pub(crate) fn to_value(&self) -> Value {
+        let mut settings = Vec::new();
+
+        if let Some(input) = &self.input {
+            settings.push(("input", input.to_value()));
+        }
+        if let Some(output) = &self.output {
+            settings.push(("output", output.to_value()));
+        }
+        if let Some(mode) = &self.mode {
+            settings.push(("mode", mode.to_value()));
+        }
+        for (key, value) in [
+            ("automatic_gain_control", self.automatic_gain_control),
+            ("echo_cancellation", self.echo_cancellation),
+            ("noise_suppression", self.noise_suppression),
+            ("qos", self.qos),
+            ("silence_warning", self.silence_warning),
+            ("deaf", self.deaf),
+            ("mute", self.mute),
+        ] {
+            if let Some(value) = value {
+                settings.push((key, value.into()));
+            }
+        }
+
+        Value::object(settings)
+    }
+}
+
+impl VoiceIo {
+    fn to_value(&self) -> Value {
+        let mut io = Vec::new();
+
+        if let Some(device_id) = &self.device_id {
+            io.push(("device_id", device_id.as_str().into()));
+        }
+        if let Some(volume) = self.volume {
+            io.push(("volume", volume.into()));
+        }
+
+        Value::object(io)
+    }
+}
+
+impl VoiceMode {
+    fn to_value(&self) -> Value {
+        let mut mode = Vec::new();
+
+        if let Some(ty) = &self.ty {
+            mode.push(("type", ty.as_str().into()));
+        }
+        if let Some(auto_threshold) = self.auto_threshold {
+            mode.push(("auto_threshold", auto_threshold.into()));
+        }
+        if let Some(threshold) = self.threshold {
+            mode.push(("threshold", threshold.into()));
+        }
+        if let Some(delay) = self.delay {
+            mode.push(("delay", delay.into()));
+        }
+
+        Value::object(mode)
+    }
+}
+
+impl Value {
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut json_str = String::new();
+        self.push_json(&mut json_str)?;
+
+        Ok(json_str)
+    }
+
+    pub fn push_json(&self, json_str: &mut String) -> Result<(), Error> {
+        match self {
+            Value::Null => json_str.push_str("null"),
+            Value::Bool(value) => write!(json_str, "{}", value)?,
+            Value::Number(value) if !value.is_finite() => {
+                json_str.push_str("null")
+            }
+            Value::Number(value)
+                if value.fract() == 0.0 && value.abs() < 9007199254740992.0 =>
+            {
+                write!(json_str, "{}", *value as i64)?
+            }
+            Value::Number(value) => write!(json_str, "{}", value)?,
+            Value::String(value) => {
+                write!(json_str, "\"{}\"", escape_json(value))?
+            }
+            Value::Array(values) => {
+                json_str.push('[');
+
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        json_str.push(',');
+                    }
+                    value.push_json(json_str)?;
+                }
+
+                json_str.push(']');
+            }
+            Value::Object(entries) => {
+                json_str.push('{');
+
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        json_str.push(',');
+                    }
+                    write!(json_str, "\"{}\":", escape_json(key))?;
+                    value.push_json(json_str)?;
+                }
+
+                json_str.push('}');
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,433 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed JSON value.
+///
+/// Objects keep their keys in the order they were read or inserted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+/// Error returned when a string is not valid JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Value {
+    pub fn object<K: Into<String>>(
+        entries: impl IntoIterator<Item = (K, Value)>,
+    ) -> Self {
+        Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Number(value) if *value >= 0.0 && value.fract() == 0.0 => {
+                Some(*value as u64)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Value::Number(value as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(values: Vec<Value>) -> Self {
+        Value::Array(values)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+impl FromStr for Value {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            offset: 0,
+            depth: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.offset != parser.input.len() {
+            return Err(parser.error("trailing characters"));
+        }
+
+        Ok(value)
+    }
+}
+
+/// How deeply arrays and objects may nest before parsing gives up, so
+/// hostile input can't overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            offset: self.offset,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), ParseError> {
+        if self.input[self.offset..].starts_with(literal.as_bytes()) {
+            self.offset += literal.len();
+            Ok(())
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.expect("null").map(|_| Value::Null),
+            Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some(b'"') => self.parse_string().map(Value::String),
+            Some(b'[') => self.nested(Self::parse_array),
+            Some(b'{') => self.nested(Self::parse_object),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value, ParseError>,
+    ) -> Result<Value, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_number(&mut self) -> Result<Value, ParseError> {
+        let start = self.offset;
+        while matches!(
+            self.peek(),
+            Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+        ) {
+            self.offset += 1;
+        }
+
+        std::str::from_utf8(&self.input[start..self.offset])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Value::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect("\"")?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.offset += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    let escape = self.input.get(self.offset + 1).copied();
+                    self.offset += 2;
+                    let escaped = match escape {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(
+                        escaped.encode_utf8(&mut buffer).as_bytes(),
+                    );
+                }
+                Some(byte) => {
+                    self.offset += 1;
+                    bytes.push(byte);
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("invalid utf-8"))
+    }
+
+    fn parse_hex(&mut self) -> Result<u32, ParseError> {
+        let code = self
+            .input
+            .get(self.offset..self.offset + 4)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.offset += 4;
+        Ok(code)
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+        let high = self.parse_hex()?;
+        let code = if (0xD800..0xDC00).contains(&high)
+            && self.input[self.offset..].starts_with(b"\\u")
+        {
+            self.offset += 2;
+            let low = self.parse_hex()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                // Not a pair: leave the second escape to be read on its own.
+                self.offset -= 6;
+                return Ok(char::REPLACEMENT_CHARACTER);
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    fn parse_array(&mut self) -> Result<Value, ParseError> {
+        self.expect("[")?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Value::Array(values));
+        }
+
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Value, ParseError> {
+        self.expect("{")?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(Value::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(":")?;
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(Value::Object(entries));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<Value, ParseError> {
+        input.parse()
+    }
+
+    fn string(input: &str) -> String {
+        match parse(input) {
+            Ok(Value::String(string)) => string,
+            other => panic!("expected a string, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(string(r#""a\"\\\/\b\f\n\r\t""#), "a\"\\/\u{8}\u{c}\n\r\t");
+        assert_eq!(string(r#""\u00e9\u4e2d""#), "é中");
+        assert_eq!(parse(r#""\x""#).unwrap_err().message, "invalid escape");
+        assert_eq!(
+            parse(r#""\u12""#).unwrap_err().message,
+            "invalid unicode escape"
+        );
+    }
+
+    #[test]
+    fn surrogates() {
+        assert_eq!(string(r#""\ud83d\ude00""#), "😀");
+        assert_eq!(string(r#""\ud800""#), "\u{fffd}");
+        assert_eq!(string(r#""\udc00x""#), "\u{fffd}x");
+        assert_eq!(string(r#""\ud800\u0041""#), "\u{fffd}A");
+        assert_eq!(string(r#""\ud800\ud83d\ude00""#), "\u{fffd}😀");
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse("0"), Ok(Value::Number(0.0)));
+        assert_eq!(parse("-12.5e2"), Ok(Value::Number(-1250.0)));
+        assert_eq!(parse("[1,2]"), Ok(Value::from(vec![1.into(), 2.into()])));
+        assert_eq!(parse("1.2.3").unwrap_err().message, "invalid number");
+        assert_eq!(parse("-").unwrap_err().message, "invalid number");
+    }
+
+    #[test]
+    fn trailing_characters() {
+        assert_eq!(parse(" {} "), Ok(Value::Object(Vec::new())));
+        let error = parse("{} x").unwrap_err();
+        assert_eq!(error.message, "trailing characters");
+        assert_eq!(error.offset, 3);
+        assert_eq!(parse("[1,]").unwrap_err().message, "unexpected character");
+        assert_eq!(parse("\"ab").unwrap_err().message, "unterminated string");
+    }
+
+    #[test]
+    fn nesting_depth() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse(&nested(MAX_DEPTH + 1)).unwrap_err().message,
+            "nesting too deep"
+        );
+        let deep = "{\"a\":".repeat(100_000);
+        assert_eq!(parse(&deep).unwrap_err().message, "nesting too deep");
+    }
+}
@@ -1,17 +1,82 @@
-#![feature(pattern)]
-
+#[cfg(all(feature = "async", not(target_os = "windows")))]
+pub mod async_client;
+pub mod clock;
+mod deferred;
+mod handle;
+pub mod history;
 mod ipc;
-mod json;
+pub mod json;
+mod manager;
 pub mod rpc;
+pub mod scene;
+pub mod schedule;
+pub mod template;
 
 use std::{
+    collections::HashMap,
     io::{self},
-    sync::{Arc, Condvar, Mutex, RwLock},
+    marker::PhantomData,
+    sync::{Arc, Condvar, Mutex, RwLock, Weak},
+    task::{Wake, Waker},
     thread::{self, JoinHandle},
+    time::Duration,
 };
 
+#[cfg(all(feature = "async", not(target_os = "windows")))]
+pub use async_client::AsyncRichClient;
+use clock::{Clock, SystemClock};
+use deferred::Deferred;
+pub use handle::{Carousel, Layer, RichClientHandle};
+use history::{History, HistoryEntry, Operation, Outcome};
 pub use ipc::client::Connection;
-use rpc::packet::{Activity, Packet};
+use ipc::client::Target;
+pub use ipc::kind::ClientKind;
+#[cfg(not(target_os = "windows"))]
+pub use ipc::platform::unix_signal::install_signal_handlers;
+pub use ipc::policy::SocketPolicy;
+use json::Value;
+pub use manager::PresenceManager;
+use manager::SharedDiscovery;
+use rpc::{
+    ack::AckedActivity,
+    activity::Activity,
+    auth::{AuthCode, AuthenticatedSession, Scope},
+    command::{Command, Response},
+    event::Event,
+    guild::{Channel, Guild},
+    voice::{VoiceChannel, VoiceSettings},
+};
+
+/// How long [`RichClient::shutdown`] waits for the listener thread to exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long [`RichClient::execute`] waits for Discord to respond.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long `AUTHORIZE` may wait for the user to answer the consent dialog.
+const AUTHORIZE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Callback receiving the name and `data` of a dispatched event.
+type EventCallback = Box<dyn Fn(&str, &Value) + Send + Sync>;
+/// Responses awaited by [`RichClient::execute`], keyed by nonce.
+type Responses = (Mutex<HashMap<String, Option<Value>>>, Condvar);
+
+/// The error message Discord responded to a request with.
+#[derive(Debug)]
+struct ErrorResponse(String);
+
+impl ErrorResponse {
+    /// Whether `err` is an error response rather than a failure to get one.
+    fn is(err: &io::Error) -> bool {
+        err.get_ref().is_some_and(|err| err.is::<ErrorResponse>())
+    }
+}
+
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ErrorResponse {}
 
 #[cfg(target_os = "windows")]
 pub struct RichClient<'a> {
@@ -21,10 +86,28 @@ pub struct RichClient<'a> {
     on_ready: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
     on_disconnect: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
     on_update: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
-    last_activity: Option<Activity<'a>>,
+    on_event: Arc<Option<EventCallback>>,
+    /// The `SET_ACTIVITY` payload of the activity last sent, with the
+    /// nonce `-`, so that an unchanged activity isn't sent again.
+    last_activity: Option<String>,
+    /// [`RichClient::update`] takes activities borrowing for `'a`.
+    activity: PhantomData<Activity<'a>>,
     signal: Arc<(Mutex<bool>, Condvar)>,
+    responses: Arc<Responses>,
+    nonce: u64,
     handle: Option<JoinHandle<Option<String>>>,
-    pipe: Arc<Option<std::fs::File>>,
+    /// Set by the listener thread as it exits.
+    exited: Arc<(Mutex<bool>, Condvar)>,
+    pipe: Arc<RwLock<Option<std::fs::File>>>,
+    clear_on_drop: bool,
+    history: Arc<Mutex<History>>,
+    target: Arc<RwLock<Option<Target>>>,
+    deferred: Option<Arc<Deferred>>,
+    socket_policy: SocketPolicy,
+    clock: Arc<dyn Clock>,
+    /// Set for the sessions of a [`PresenceManager`], which look for
+    /// Discord together.
+    discovery: Option<Arc<SharedDiscovery>>,
 }
 
 #[cfg(not(target_os = "windows"))]
@@ -35,9 +118,28 @@ pub struct RichClient<'a> {
     on_ready: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
     on_disconnect: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
     on_update: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
-    last_activity: Option<Activity<'a>>,
+    on_event: Arc<Option<EventCallback>>,
+    /// The `SET_ACTIVITY` payload of the activity last sent, with the
+    /// nonce `-`, so that an unchanged activity isn't sent again.
+    last_activity: Option<String>,
+    /// [`RichClient::update`] takes activities borrowing for `'a`.
+    activity: PhantomData<Activity<'a>>,
     signal: Arc<(Mutex<bool>, Condvar)>,
+    responses: Arc<Responses>,
+    nonce: u64,
+    handle: Option<JoinHandle<Option<String>>>,
+    /// Set by the listener thread as it exits.
+    exited: Arc<(Mutex<bool>, Condvar)>,
     pipe: Arc<RwLock<Option<std::os::unix::net::UnixStream>>>,
+    clear_on_drop: bool,
+    history: Arc<Mutex<History>>,
+    target: Arc<RwLock<Option<Target>>>,
+    deferred: Option<Arc<Deferred>>,
+    socket_policy: SocketPolicy,
+    clock: Arc<dyn Clock>,
+    /// Set for the sessions of a [`PresenceManager`], which look for
+    /// Discord together.
+    discovery: Option<Arc<SharedDiscovery>>,
 }
 
 impl<'a> RichClient<'a> {
@@ -48,15 +150,100 @@ impl<'a> RichClient<'a> {
             on_ready: Arc::default(),
             on_disconnect: Arc::default(),
             on_update: Arc::default(),
+            on_event: Arc::default(),
             pipe: Arc::default(),
             last_activity: None,
+            activity: PhantomData,
             pid: std::process::id(),
             handle: None,
+            exited: Arc::default(),
             signal: Arc::default(),
+            responses: Arc::default(),
+            nonce: 0,
+            clear_on_drop: false,
+            history: Arc::default(),
+            target: Arc::default(),
+            deferred: None,
+            socket_policy: SocketPolicy::default(),
+            clock: Arc::new(SystemClock),
+            discovery: None,
         }
     }
 
+    /// Whether to clear the presence before disconnecting when the client
+    /// is dropped. Disabled by default.
+    pub fn clear_on_drop(mut self, clear_on_drop: bool) -> Self {
+        self.clear_on_drop = clear_on_drop;
+        self
+    }
+
+    /// How many presence operations [`RichClient::history`] keeps.
+    /// Defaults to 32.
+    pub fn history_capacity(self, capacity: usize) -> Self {
+        self.history.lock().unwrap().set_capacity(capacity);
+        self
+    }
+
+    /// The clock timing behavior reads, such as history timestamps, the
+    /// handle's rate limiting and timeouts, reconnect backoff and response
+    /// timeouts. Defaults to [`SystemClock`](clock::SystemClock).
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self.clock.register_waker(Waker::from(Arc::new(ClockWaker(
+            Arc::downgrade(&self.responses),
+        ))));
+        self.history.lock().unwrap().clock = Arc::clone(&self.clock);
+        self
+    }
+
+    /// Which discovered sockets to trust. Defaults to
+    /// [`SocketPolicy::Any`].
+    pub fn socket_policy(mut self, policy: SocketPolicy) -> Self {
+        self.socket_policy = policy;
+        self
+    }
+
+    /// Enables deferred mode, in which the client connects in the
+    /// background whenever Discord is running and reconnects after it
+    /// restarts.
+    ///
+    /// [`RichClient::update`] and [`RichClient::clear`] then always succeed
+    /// while Discord is unavailable: they store the desired presence, which
+    /// is applied as soon as the connection is ready. Calling
+    /// [`RichClient::connect`] is optional.
+    pub fn deferred(mut self, deferred: bool) -> Self {
+        self.deferred = deferred.then(Arc::default);
+        self
+    }
+
+    /// Connects to Discord. With `should_block`, this waits until Discord
+    /// is ready, failing if the connection is closed first, such as when
+    /// Discord rejects the client id.
+    ///
+    /// In deferred mode, this starts the background connection instead and
+    /// returns immediately, ignoring `should_block`.
     pub fn connect(&mut self, should_block: bool) -> io::Result<()> {
+        if let Some(deferred) = self.deferred.clone() {
+            self.perform_check()?;
+            if self.handle.is_none() {
+                #[cfg(not(target_os = "windows"))]
+                ipc::platform::unix_signal::register(
+                    Arc::downgrade(&self.pipe),
+                    self.pid,
+                    self.client_id,
+                );
+                self.handle = Some(deferred::spawn(
+                    self.listener(),
+                    deferred,
+                    self.socket_policy,
+                    Arc::clone(&self.clock),
+                    self.discovery.clone(),
+                    self.exit_signal(),
+                ));
+            }
+            return Ok(());
+        }
+
         if *self.connection_state.read().unwrap()
             != ConnectionState::Disconnected
         {
@@ -64,6 +251,14 @@ impl<'a> RichClient<'a> {
         }
 
         self.open()?;
+        // The activity last sent was lost with the previous connection.
+        self.last_activity = None;
+        #[cfg(not(target_os = "windows"))]
+        ipc::platform::unix_signal::register(
+            Arc::downgrade(&self.pipe),
+            self.pid,
+            self.client_id,
+        );
         *self.connection_state.write().unwrap() = ConnectionState::Connected;
         self.handshake()?;
         self.listen();
@@ -72,6 +267,18 @@ impl<'a> RichClient<'a> {
             let (lock, cvar) = &*self.signal;
             let mut started = lock.lock().unwrap();
             while !*started {
+                if *self.connection_state.read().unwrap()
+                    == ConnectionState::Disconnected
+                {
+                    drop(started);
+                    return Err(match self.join(SHUTDOWN_TIMEOUT) {
+                        Some(err) => io::Error::other(err),
+                        None => io::Error::new(
+                            io::ErrorKind::ConnectionAborted,
+                            "Connection closed before Discord was ready",
+                        ),
+                    });
+                }
                 started = cvar.wait(started).unwrap();
             }
             *started = false;
@@ -81,152 +288,844 @@ impl<'a> RichClient<'a> {
     }
 
     pub fn clear(&mut self) -> io::Result<()> {
-        self.perform_check()?;
+        if let Some(deferred) = self.deferred.clone() {
+            *deferred.desired.lock().unwrap() = None;
+            if self.start_deferred()? {
+                let _ = self.send_clear();
+            }
+            return Ok(());
+        }
 
-        self.write(
-            1,
-            Some(Packet::new(self.pid, None).to_json().unwrap().as_bytes()),
-        )
+        self.send_clear()
     }
 
     pub fn update(&mut self, activity: Activity<'a>) -> io::Result<()> {
-        println!("update");
-        self.perform_check()?;
-        println!("perform_check");
-        if self.last_activity.as_ref() != Some(&activity) {
-            self.write(
-                1,
-                Some(
-                    Packet::new(self.pid, Some(&activity))
-                        .to_json()
-                        .unwrap()
-                        .as_bytes(),
-                ),
+        if let Some(deferred) = self.deferred.clone() {
+            let payload = Command::SetActivity {
+                pid: self.pid,
+                activity: Some(&activity),
+            }
+            .to_json("-")
+            .unwrap();
+            // The listener reads this when the connection becomes ready, so
+            // it must not stay locked while connecting or writing.
+            *deferred.desired.lock().unwrap() = Some(payload);
+            if self.start_deferred()? {
+                let _ = self.send_update(activity);
+            }
+            return Ok(());
+        }
+
+        self.send_update(activity)
+    }
+
+    fn send_clear(&mut self) -> io::Result<()> {
+        let nonce = self.next_nonce();
+        self.set_activity(
+            Operation::Clear,
+            &nonce,
+            &Command::SetActivity {
+                pid: self.pid,
+                activity: None,
+            }
+            .to_json(&nonce)
+            .unwrap(),
+        )?;
+        self.last_activity = None;
+
+        Ok(())
+    }
+
+    fn send_update(&mut self, activity: Activity<'a>) -> io::Result<()> {
+        let command = Command::SetActivity {
+            pid: self.pid,
+            activity: Some(&activity),
+        };
+        let payload = command.to_json("-").unwrap();
+        if self.last_activity.as_ref() != Some(&payload) {
+            let nonce = self.next_nonce();
+            self.set_activity(
+                Operation::Update,
+                &nonce,
+                &command.to_json(&nonce).unwrap(),
             )?;
-            self.last_activity = Some(activity);
+            self.last_activity = Some(payload);
+        } else {
+            self.perform_check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a `SET_ACTIVITY` payload with the nonce `-`, as built by a
+    /// [`RichClientHandle`], unless it is the activity last sent.
+    ///
+    /// In deferred mode, the payload is kept for when Discord becomes
+    /// available, like [`RichClient::update`] does.
+    pub(crate) fn send_payload(&mut self, payload: &str) -> io::Result<()> {
+        if let Some(deferred) = self.deferred.clone() {
+            *deferred.desired.lock().unwrap() = Some(payload.to_string());
+            if !self.start_deferred()? {
+                return Ok(());
+            }
+        }
+
+        if self.last_activity.as_deref() != Some(payload) {
+            self.set_activity(Operation::Update, "-", payload)?;
+            self.last_activity = Some(payload.to_string());
         }
 
         Ok(())
     }
 
+    /// Starts the background connection if needed and returns whether it
+    /// is ready to send to.
+    fn start_deferred(&mut self) -> io::Result<bool> {
+        self.connect(false)?;
+        Ok(*self.connection_state.read().unwrap()
+            == ConnectionState::SentHandshake)
+    }
+
+    /// Like [`RichClient::update`], but waits for Discord to acknowledge
+    /// the activity and returns what it actually stored.
+    ///
+    /// The activity is always sent, even if it equals the last one.
+    pub fn update_acked(
+        &mut self,
+        activity: Activity<'a>,
+    ) -> io::Result<AckedActivity> {
+        let command = Command::SetActivity {
+            pid: self.pid,
+            activity: Some(&activity),
+        };
+        let nonce = self.next_nonce();
+        if let Err(e) = self
+            .send_request(&nonce, command.to_json(&nonce).unwrap().as_bytes())
+        {
+            self.record(
+                Operation::Update,
+                &nonce,
+                Outcome::Failed(e.to_string()),
+            );
+            return Err(e);
+        }
+        self.record(Operation::Update, &nonce, Outcome::Sent);
+
+        let response = self
+            .wait_response(command.name(), &nonce)
+            .and_then(|data| Response::from_data(&command, data))
+            .inspect_err(|e| {
+                // Error responses are already recorded by the listener.
+                if !ErrorResponse::is(e) {
+                    self.record(
+                        Operation::Update,
+                        &nonce,
+                        Outcome::Failed(e.to_string()),
+                    );
+                }
+            })?;
+        self.last_activity = Some(command.to_json("-").unwrap());
+
+        match response {
+            Response::SetActivity(Some(acked)) => Ok(*acked),
+            Response::SetActivity(None) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Discord did not return the activity",
+            )),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The kind of client the last connection was made to, or `None` if
+    /// the client never connected.
+    pub fn client_kind(&self) -> Option<ClientKind> {
+        self.target
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|target| target.kind)
+    }
+
+    /// The sockets [`SocketPolicy`] skipped before the last connection was
+    /// made, each with the reason. Empty if none were skipped or the client
+    /// never connected.
+    pub fn skipped_sockets(&self) -> Vec<String> {
+        self.target
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|target| target.skipped.clone())
+            .unwrap_or_default()
+    }
+
+    /// The most recent presence operations, oldest first.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.history.lock().unwrap().entries()
+    }
+
+    /// The activity Discord last acknowledged, which may differ from the
+    /// last one sent. `None` if the presence is cleared or nothing has
+    /// been acknowledged yet.
+    pub fn current(&self) -> Option<AckedActivity> {
+        self.history.lock().unwrap().current.clone()
+    }
+
+    /// Sends a typed command and blocks until Discord responds to it.
+    pub fn execute(&mut self, command: &Command) -> io::Result<Response> {
+        let nonce = self.next_nonce();
+        let data = self.request(
+            command.name(),
+            &nonce,
+            command.to_json(&nonce).unwrap().as_bytes(),
+        )?;
+
+        Response::from_data(command, data)
+    }
+
+    /// Asks the user to authorize the application for `scopes`, which
+    /// shows Discord's consent dialog. Blocks until the user answers it.
+    pub fn authorize(&mut self, scopes: &[Scope]) -> io::Result<AuthCode> {
+        match self.execute(&Command::Authorize {
+            client_id: self.client_id,
+            scopes,
+            rpc_token: None,
+            username: None,
+        })? {
+            Response::Authorize(code) => Ok(code),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Authenticates the connection with an OAuth2 access token, which is
+    /// required by most commands other than `SET_ACTIVITY`.
+    pub fn authenticate(
+        &mut self,
+        access_token: &str,
+    ) -> io::Result<AuthenticatedSession> {
+        match self.execute(&Command::Authenticate { access_token })? {
+            Response::Authenticate(session) => Ok(*session),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Runs the full OAuth2 flow: [`RichClient::authorize`], then
+    /// `exchange` to trade the code for an access token, then
+    /// [`RichClient::authenticate`].
+    ///
+    /// The exchange needs the application's client secret and network
+    /// access, so it is left to the caller.
+    pub fn login(
+        &mut self,
+        scopes: &[Scope],
+        exchange: impl FnOnce(&AuthCode) -> io::Result<String>,
+    ) -> io::Result<AuthenticatedSession> {
+        let code = self.authorize(scopes)?;
+        let access_token = exchange(&code)?;
+        self.authenticate(&access_token)
+    }
+
+    /// Subscribes to `event`. Its dispatches are delivered to the
+    /// callback registered with [`RichClient::on_event`].
+    pub fn subscribe(&mut self, event: Event) -> io::Result<()> {
+        self.execute(&Command::Subscribe(event)).map(|_| ())
+    }
+
+    pub fn unsubscribe(&mut self, event: Event) -> io::Result<()> {
+        self.execute(&Command::Unsubscribe(event)).map(|_| ())
+    }
+
+    pub fn voice_settings(&mut self) -> io::Result<VoiceSettings> {
+        match self.execute(&Command::GetVoiceSettings)? {
+            Response::GetVoiceSettings(settings) => Ok(settings),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Changes the fields that are set in `settings` and returns the
+    /// resulting voice settings.
+    pub fn set_voice_settings(
+        &mut self,
+        settings: &VoiceSettings,
+    ) -> io::Result<VoiceSettings> {
+        match self.execute(&Command::SetVoiceSettings(settings))? {
+            Response::SetVoiceSettings(settings) => Ok(settings),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Joins the voice channel `channel_id`, or leaves voice if it is
+    /// `None`. `force` moves the user even if they are already in another
+    /// voice channel.
+    pub fn select_voice_channel(
+        &mut self,
+        channel_id: Option<&str>,
+        force: bool,
+    ) -> io::Result<Option<VoiceChannel>> {
+        match self.execute(&Command::SelectVoiceChannel {
+            channel_id,
+            timeout: None,
+            force,
+        })? {
+            Response::SelectVoiceChannel(channel) => Ok(channel),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn selected_voice_channel(
+        &mut self,
+    ) -> io::Result<Option<VoiceChannel>> {
+        match self.execute(&Command::GetSelectedVoiceChannel)? {
+            Response::GetSelectedVoiceChannel(channel) => Ok(channel),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn guilds(&mut self) -> io::Result<Vec<Guild>> {
+        match self.execute(&Command::GetGuilds)? {
+            Response::GetGuilds(guilds) => Ok(guilds),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn guild(&mut self, guild_id: &str) -> io::Result<Guild> {
+        match self.execute(&Command::GetGuild {
+            guild_id,
+            timeout: None,
+        })? {
+            Response::GetGuild(guild) => Ok(guild),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Lists the channels of a guild. Only their id, name and type are
+    /// filled in; use [`RichClient::channel`] for the rest.
+    pub fn channels(&mut self, guild_id: &str) -> io::Result<Vec<Channel>> {
+        match self.execute(&Command::GetChannels { guild_id })? {
+            Response::GetChannels(channels) => Ok(channels),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn channel(&mut self, channel_id: &str) -> io::Result<Channel> {
+        match self.execute(&Command::GetChannel { channel_id })? {
+            Response::GetChannel(channel) => Ok(channel),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Sends an arbitrary RPC command and blocks until Discord responds to
+    /// it, returning the `data` field of the response.
+    ///
+    /// This covers commands the typed API doesn't support yet. `evt` is
+    /// only needed by commands such as `SUBSCRIBE`. An `ERROR` response is
+    /// returned as an error carrying Discord's message.
+    pub fn send_command(
+        &mut self,
+        cmd: &str,
+        args: Value,
+        evt: Option<&str>,
+    ) -> io::Result<Value> {
+        let nonce = self.next_nonce();
+        let mut message = vec![("cmd", Value::from(cmd)), ("args", args)];
+        if let Some(evt) = evt {
+            message.push(("evt", evt.into()));
+        }
+        message.push(("nonce", nonce.as_str().into()));
+
+        self.request(
+            cmd,
+            &nonce,
+            Value::object(message).to_json().unwrap().as_bytes(),
+        )
+    }
+
+    /// Registers a callback for events dispatched by Discord, such as
+    /// those subscribed to with [`Command::Subscribe`]. It receives the
+    /// event name and its `data`.
+    pub fn on_event(
+        mut self,
+        on_event: impl Fn(&str, &Value) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_event = Arc::new(Some(Box::new(on_event)));
+        self
+    }
+
+    /// Sends the close frame, shuts the socket down and waits for the
+    /// listener thread to exit.
     pub fn shutdown(&mut self) -> io::Result<()> {
+        if let Some(deferred) = &self.deferred {
+            deferred.stop();
+        }
         if *self.connection_state.read().unwrap()
             == ConnectionState::Disconnected
         {
+            self.join(SHUTDOWN_TIMEOUT);
             return Ok(());
         }
 
-        self.close()?;
-        let mut state = self.connection_state.write().unwrap();
-        *state = ConnectionState::Disconnected;
+        let result = self.close();
+        // The listener may have noticed the disconnect in the meantime, in
+        // which case it already notified `on_disconnect`.
+        let was_connected =
+            std::mem::take(&mut *self.connection_state.write().unwrap())
+                != ConnectionState::Disconnected;
         self.last_activity = None;
+        self.history.lock().unwrap().current = None;
+        self.join(SHUTDOWN_TIMEOUT);
 
-        if let Some(on_disconnect) = self.on_disconnect.as_ref() {
-            on_disconnect();
+        if was_connected {
+            if let Some(on_disconnect) = self.on_disconnect.as_ref() {
+                on_disconnect();
+            }
         }
 
-        Ok(())
+        result
+    }
+
+    /// Clears the presence if [`RichClient::clear_on_drop`] is set and the
+    /// client is connected. Must run before [`RichClient::shutdown`], which
+    /// marks the client disconnected.
+    pub(crate) fn clear_on_shutdown(&mut self) {
+        if self.clear_on_drop
+            && *self.connection_state.read().unwrap()
+                != ConnectionState::Disconnected
+        {
+            let _ = self.clear();
+        }
+    }
+
+    /// Moves the client onto a worker thread and returns a cloneable handle
+    /// to it. See [`RichClientHandle`].
+    ///
+    /// Behavior that changes the presence over time, such as layers with a
+    /// TTL, carousels, schedules and the idle policy, runs on that thread
+    /// and is only available through the handle.
+    pub fn into_handle(self) -> RichClientHandle
+    where
+        'a: 'static,
+    {
+        RichClientHandle::new(self)
     }
 
     fn handshake(&mut self) -> io::Result<()> {
-        self.write(
+        RichClient::_handshake(&self.pipe, self.client_id)
+    }
+
+    pub(crate) fn _handshake(
+        #[cfg(target_os = "windows")] pipe: &RwLock<Option<std::fs::File>>,
+        #[cfg(not(target_os = "windows"))] pipe: &RwLock<
+            Option<std::os::unix::net::UnixStream>,
+        >,
+        client_id: u64,
+    ) -> io::Result<()> {
+        RichClient::_write(
+            pipe,
             0,
             Some(
-                format!("{{\"v\": 1,\"client_id\":\"{}\"}}", self.client_id)
+                format!("{{\"v\": 1,\"client_id\":\"{}\"}}", client_id)
                     .as_bytes(),
             ),
         )
     }
 
+    fn listener(&self) -> Listener {
+        Listener {
+            client_id: self.client_id,
+            signal: Arc::clone(&self.signal),
+            connection_state: Arc::clone(&self.connection_state),
+            pipe: Arc::clone(&self.pipe),
+            on_ready: Arc::clone(&self.on_ready),
+            on_disconnect: Arc::clone(&self.on_disconnect),
+            on_update: Arc::clone(&self.on_update),
+            on_event: Arc::clone(&self.on_event),
+            responses: Arc::clone(&self.responses),
+            history: Arc::clone(&self.history),
+            target: Arc::clone(&self.target),
+            deferred: self.deferred.clone(),
+        }
+    }
+
     fn listen(&mut self) {
-        let client_id = self.client_id;
-        let signal = Arc::clone(&self.signal);
-        let connection_state = Arc::clone(&self.connection_state);
-        let pipe = Arc::clone(&self.pipe);
-        let on_ready = Arc::clone(&self.on_ready);
-        let on_disconnect = Arc::clone(&self.on_disconnect);
-        let on_update = Arc::clone(&self.on_update);
+        let listener = self.listener();
+        let exit = self.exit_signal();
         self.handle = Some(thread::spawn(move || {
-            while *connection_state.read().unwrap()
-                != ConnectionState::Disconnected
-            {
-                let (op, data) = match RichClient::read(&pipe) {
-                    Ok(data) => data,
-                    Err(_) => return None,
-                };
-                println!("op: {}", op);
+            let _exit = exit;
+            let rejected = listener.run();
+            listener.disconnected();
+            rejected
+        }));
+    }
+
+    /// Writes a `SET_ACTIVITY` payload and records it in the history.
+    fn set_activity(
+        &mut self,
+        operation: Operation,
+        nonce: &str,
+        payload: &str,
+    ) -> io::Result<()> {
+        let result = self
+            .perform_check()
+            .and_then(|_| self.write(1, Some(payload.as_bytes())));
+        self.record(
+            operation,
+            nonce,
+            match &result {
+                Ok(()) => Outcome::Sent,
+                Err(e) => Outcome::Failed(e.to_string()),
+            },
+        );
+
+        result
+    }
+
+    fn record(&self, operation: Operation, nonce: &str, outcome: Outcome) {
+        self.history.lock().unwrap().push(
+            operation,
+            self.target
+                .read()
+                .unwrap()
+                .as_ref()
+                .map(|target| target.path.as_str()),
+            nonce,
+            outcome,
+        );
+    }
+
+    fn next_nonce(&mut self) -> String {
+        self.nonce += 1;
+        format!("{}-{}", self.pid, self.nonce)
+    }
+
+    /// Writes `payload` and waits for the response carrying `nonce`,
+    /// returning its `data`.
+    fn request(
+        &mut self,
+        cmd: &str,
+        nonce: &str,
+        payload: &[u8],
+    ) -> io::Result<Value> {
+        self.send_request(nonce, payload)?;
+        self.wait_response(cmd, nonce)
+    }
+
+    /// Writes `payload`, expecting a response carrying `nonce`.
+    fn send_request(&mut self, nonce: &str, payload: &[u8]) -> io::Result<()> {
+        self.perform_check()?;
+
+        let responses = Arc::clone(&self.responses);
+        responses.0.lock().unwrap().insert(nonce.to_string(), None);
+        if let Err(e) = self.write(1, Some(payload)) {
+            responses.0.lock().unwrap().remove(nonce);
+            return Err(e);
+        }
 
-                if *connection_state.read().unwrap()
+        Ok(())
+    }
+
+    /// Waits for the response carrying `nonce` to a request sent with
+    /// [`RichClient::send_request`], returning its `data`. Error responses
+    /// are returned as an [`ErrorResponse`].
+    fn wait_response(&self, cmd: &str, nonce: &str) -> io::Result<Value> {
+        let (lock, cvar) = &*self.responses;
+        let deadline = self.clock.now()
+            + match cmd {
+                "AUTHORIZE" => AUTHORIZE_TIMEOUT,
+                _ => RESPONSE_TIMEOUT,
+            };
+        let mut responses = lock.lock().unwrap();
+        let response = loop {
+            if let Some(Some(_)) = responses.get(nonce) {
+                break responses.remove(nonce).flatten().unwrap();
+            }
+
+            if self.clock.now() >= deadline
+                || *self.connection_state.read().unwrap()
                     == ConnectionState::Disconnected
-                {
-                    break;
+            {
+                responses.remove(nonce);
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("No response to {cmd}"),
+                ));
+            }
+            responses = match self.clock.timeout(deadline) {
+                Some(timeout) => {
+                    cvar.wait_timeout(responses, timeout).unwrap().0
                 }
+                None => cvar.wait(responses).unwrap(),
+            };
+        };
+        drop(responses);
 
-                println!(
-                    "message: {}",
-                    String::from_utf8(data.clone()).unwrap()
-                );
+        let data = response.get("data").cloned().unwrap_or_default();
+        if response.get("evt").and_then(Value::as_str) == Some("ERROR") {
+            return Err(io::Error::other(ErrorResponse(
+                data.get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            )));
+        }
 
-                match op {
-                    1 => {
-                        if *connection_state.read().unwrap()
-                            == ConnectionState::Connected
-                        {
-                            *connection_state.write().unwrap() =
-                                ConnectionState::SentHandshake;
-                            *signal.0.lock().unwrap() = true;
-                            signal.1.notify_one();
-
-                            if let Some(on_ready) = on_ready.as_ref() {
-                                on_ready();
-                            }
-
-                            continue;
-                        }
+        Ok(data)
+    }
 
-                        if let Some(on_update) = on_update.as_ref() {
-                            on_update();
-                        }
-                    }
-                    2 => {
-                        if String::from_utf8(data)
-                            .unwrap()
-                            .contains("Invalid Client ID")
-                        {
-                            return Some("Invalid Client ID".to_string());
-                        }
-                        if *connection_state.read().unwrap()
-                            != ConnectionState::Disconnected
-                        {
-                            *connection_state.write().unwrap() =
-                                ConnectionState::Disconnected;
-                            let _ = RichClient::_close(&pipe, client_id);
-                            signal.1.notify_one();
-                            if let Some(on_disconnect) = on_disconnect.as_ref()
-                            {
-                                on_disconnect();
-                            }
-                        }
-                    }
-                    _ => {}
-                }
+    /// Routes a message received from Discord: responses go to the
+    /// [`RichClient::request`] waiting for their nonce, dispatched events
+    /// to the `on_event` callback. Responses to `SET_ACTIVITY` are also
+    /// recorded in the history.
+    fn dispatch(
+        responses: &Responses,
+        on_event: &Option<EventCallback>,
+        history: &Mutex<History>,
+        target: Option<&str>,
+        data: &[u8],
+    ) {
+        let Some(message) = std::str::from_utf8(data)
+            .ok()
+            .and_then(|data| data.parse::<Value>().ok())
+        else {
+            return;
+        };
+
+        if message.get("cmd").and_then(Value::as_str) == Some("DISPATCH") {
+            if let (Some(on_event), Some(evt)) =
+                (on_event, message.get("evt").and_then(Value::as_str))
+            {
+                on_event(evt, message.get("data").unwrap_or(&Value::Null));
             }
+            return;
+        }
 
-            None
-        }));
+        let Some(nonce) = message.get("nonce").and_then(Value::as_str) else {
+            return;
+        };
+
+        if message.get("cmd").and_then(Value::as_str) == Some("SET_ACTIVITY") {
+            let data = message.get("data").unwrap_or(&Value::Null);
+            let mut history = history.lock().unwrap();
+            let outcome = if message.get("evt").and_then(Value::as_str)
+                == Some("ERROR")
+            {
+                Outcome::Failed(
+                    data.get("message")
+                        .and_then(Value::as_str)
+                        .unwrap_or("Unknown error")
+                        .to_string(),
+                )
+            } else {
+                let acked =
+                    (!data.is_null()).then(|| AckedActivity::from_value(data));
+                history.current = acked.clone();
+                Outcome::Acked(acked.map(Box::new))
+            };
+            history.push(Operation::Ack, target, nonce, outcome);
+        }
+
+        let mut pending = responses.0.lock().unwrap();
+        if let Some(slot) = pending.get_mut(nonce) {
+            *slot = Some(message);
+            responses.1.notify_all();
+        }
     }
 
     fn perform_check(&mut self) -> io::Result<()> {
-        if let Some(handle) = self.handle.take() {
-            if handle.is_finished() {
-                if let Ok(Some(err)) = handle.join() {
-                    return Err(io::Error::new(io::ErrorKind::Other, err));
-                }
+        if self.handle.as_ref().is_some_and(JoinHandle::is_finished) {
+            if let Some(Ok(Some(err))) = self.handle.take().map(|h| h.join()) {
+                return Err(io::Error::other(err));
             }
         };
 
         Ok(())
     }
+
+    /// Joins the listener thread, giving up after `timeout`, and returns
+    /// the error message it exited with if Discord rejected the client. A
+    /// thread that is still blocked by then is detached.
+    fn join(&mut self, timeout: Duration) -> Option<String> {
+        let handle = self.handle.take()?;
+
+        let (lock, cvar) = &*self.exited;
+        let deadline = self.clock.now() + timeout;
+        let mut guard = lock.lock().unwrap();
+        while !*guard && self.clock.now() < deadline {
+            guard = match self.clock.timeout(deadline) {
+                Some(timeout) => cvar.wait_timeout(guard, timeout).unwrap().0,
+                None => cvar.wait(guard).unwrap(),
+            };
+        }
+        let exited = *guard;
+        drop(guard);
+        if exited {
+            return handle.join().ok().flatten();
+        }
+
+        None
+    }
+
+    /// A fresh signal for a listener thread about to be spawned to set
+    /// when it exits.
+    fn exit_signal(&mut self) -> ExitSignal {
+        self.exited = Arc::default();
+        self.clock.register_waker(Waker::from(Arc::new(ClockWaker(
+            Arc::downgrade(&self.exited),
+        ))));
+        ExitSignal(Arc::clone(&self.exited))
+    }
+}
+
+/// Wakes up threads waiting on a condition variable, such as for a
+/// response or for the listener thread to exit, when the clock moves.
+struct ClockWaker<T>(Weak<(Mutex<T>, Condvar)>);
+
+impl<T: Send> Wake for ClockWaker<T> {
+    fn wake(self: Arc<Self>) {
+        if let Some(pair) = self.0.upgrade() {
+            // Taking the lock ensures a waiting thread is either notified or
+            // sees the new time before it blocks.
+            let _guard = pair.0.lock().unwrap();
+            pair.1.notify_all();
+        }
+    }
+}
+
+/// Marks a listener thread as exited when dropped, including when the
+/// thread panics.
+pub(crate) struct ExitSignal(Arc<(Mutex<bool>, Condvar)>);
+
+impl Drop for ExitSignal {
+    fn drop(&mut self) {
+        *self.0 .0.lock().unwrap() = true;
+        self.0 .1.notify_all();
+    }
+}
+
+impl Drop for RichClient<'_> {
+    fn drop(&mut self) {
+        self.clear_on_shutdown();
+        let _ = self.shutdown();
+    }
+}
+
+/// The state shared with the thread reading from the socket.
+struct Listener {
+    client_id: u64,
+    signal: Arc<(Mutex<bool>, Condvar)>,
+    connection_state: Arc<RwLock<ConnectionState>>,
+    #[cfg(target_os = "windows")]
+    pipe: Arc<RwLock<Option<std::fs::File>>>,
+    #[cfg(not(target_os = "windows"))]
+    pipe: Arc<RwLock<Option<std::os::unix::net::UnixStream>>>,
+    on_ready: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
+    on_disconnect: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
+    on_update: Arc<Option<Box<dyn Fn() + Send + Sync>>>,
+    on_event: Arc<Option<EventCallback>>,
+    responses: Arc<Responses>,
+    history: Arc<Mutex<History>>,
+    target: Arc<RwLock<Option<Target>>>,
+    deferred: Option<Arc<Deferred>>,
+}
+
+impl Listener {
+    /// Reads messages until the connection is closed. Returns an error
+    /// message if Discord rejected the client.
+    fn run(&self) -> Option<String> {
+        let target = self
+            .target
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|target| target.path.clone());
+        while *self.connection_state.read().unwrap()
+            != ConnectionState::Disconnected
+        {
+            let (op, data) = match RichClient::read(&self.pipe) {
+                Ok(data) => data,
+                Err(_) => return None,
+            };
+
+            if *self.connection_state.read().unwrap()
+                == ConnectionState::Disconnected
+            {
+                break;
+            }
+
+            match op {
+                1 => {
+                    if *self.connection_state.read().unwrap()
+                        == ConnectionState::Connected
+                    {
+                        *self.connection_state.write().unwrap() =
+                            ConnectionState::SentHandshake;
+                        if let Some(deferred) = &self.deferred {
+                            deferred.apply(self, target.as_deref());
+                        }
+                        *self.signal.0.lock().unwrap() = true;
+                        self.signal.1.notify_one();
+
+                        if let Some(on_ready) = self.on_ready.as_ref() {
+                            on_ready();
+                        }
+
+                        continue;
+                    }
+
+                    RichClient::dispatch(
+                        &self.responses,
+                        &self.on_event,
+                        &self.history,
+                        target.as_deref(),
+                        &data,
+                    );
+
+                    if let Some(on_update) = self.on_update.as_ref() {
+                        on_update();
+                    }
+                }
+                2 => {
+                    if String::from_utf8_lossy(&data)
+                        .contains("Invalid Client ID")
+                    {
+                        return Some("Invalid Client ID".to_string());
+                    }
+                    if *self.connection_state.read().unwrap()
+                        != ConnectionState::Disconnected
+                    {
+                        let _ = RichClient::_close(&self.pipe, self.client_id);
+                        self.disconnected();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Marks the connection as lost and notifies everyone waiting on it,
+    /// unless it was already.
+    fn disconnected(&self) {
+        if std::mem::take(&mut *self.connection_state.write().unwrap())
+            == ConnectionState::Disconnected
+        {
+            return;
+        }
+        // Taking the locks ensures that threads waiting for the connection
+        // to become ready or for a response are either notified or see the
+        // new state before they block.
+        {
+            let _started = self.signal.0.lock().unwrap();
+            self.signal.1.notify_one();
+        }
+        {
+            let _responses = self.responses.0.lock().unwrap();
+            self.responses.1.notify_all();
+        }
+        self.history.lock().unwrap().current = None;
+        if let Some(on_disconnect) = self.on_disconnect.as_ref() {
+            on_disconnect();
+        }
+    }
 }
 
 #[derive(PartialEq, Default)]
@@ -1,4 +1,4 @@
-use rpresence::{rpc::packet::Activity, RichClient};
+use rpresence::{rpc::activity::Activity, RichClient};
 
 fn main() {
     let mut client = RichClient::new(1219918645770059796);
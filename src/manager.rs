@@ -0,0 +1,311 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::clock::{Clock, SystemClock};
+use crate::deferred::Deferred;
+use crate::ipc::client::Target;
+#[cfg(not(target_os = "windows"))]
+use crate::ipc::platform::unix_connection::{open_path, open_pipe};
+#[cfg(target_os = "windows")]
+use crate::ipc::platform::windows_connection::{open_path, open_pipe};
+use crate::json::Value;
+use crate::rpc::activity::Activity;
+use crate::{ClientKind, ConnectionState, RichClient, SocketPolicy};
+
+#[cfg(target_os = "windows")]
+type Pipe = std::fs::File;
+#[cfg(not(target_os = "windows"))]
+type Pipe = std::os::unix::net::UnixStream;
+
+/// Callback receiving the client id of the session an event was dispatched
+/// to, along with the event name and its `data`.
+type SessionEventCallback = Box<dyn Fn(u64, &str, &Value) + Send + Sync>;
+
+/// Shows presence for several Discord applications, one at a time.
+///
+/// Each application gets its own [`RichClient`] session, connected over a
+/// separate socket with its own `pid`. Only the active session shows a
+/// presence: switching to another application clears the previous one.
+///
+/// Sessions share the socket policy, deferred mode, clock and event
+/// callback configured on the manager, and events from every session are
+/// routed to that one callback.
+///
+/// Sessions also share discovery: they all connect to the socket the first
+/// one found, so that they reach the same Discord client, and look for a
+/// new one once it is gone. In deferred mode, a session that finds Discord
+/// wakes the others waiting for it, so that they reconnect together.
+/// Otherwise, a session that lost its connection reconnects when the
+/// manager next uses it.
+pub struct PresenceManager<'a> {
+    sessions: Vec<RichClient<'a>>,
+    active: Option<u64>,
+    socket_policy: SocketPolicy,
+    deferred: bool,
+    on_event: Arc<Option<SessionEventCallback>>,
+    clock: Arc<dyn Clock>,
+    discovery: Arc<SharedDiscovery>,
+}
+
+impl Default for PresenceManager<'_> {
+    fn default() -> Self {
+        Self {
+            sessions: Vec::new(),
+            active: None,
+            socket_policy: SocketPolicy::default(),
+            deferred: false,
+            on_event: Arc::default(),
+            clock: Arc::new(SystemClock),
+            discovery: Arc::default(),
+        }
+    }
+}
+
+impl<'a> PresenceManager<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Which discovered sockets sessions trust. See
+    /// [`RichClient::socket_policy`].
+    pub fn socket_policy(mut self, policy: SocketPolicy) -> Self {
+        self.socket_policy = policy;
+        self
+    }
+
+    /// Whether sessions connect in the background and reconnect after
+    /// Discord restarts. See [`RichClient::deferred`].
+    pub fn deferred(mut self, deferred: bool) -> Self {
+        self.deferred = deferred;
+        self
+    }
+
+    /// The clock sessions read time from. See [`RichClient::clock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Registers a callback for events dispatched to any session. It
+    /// receives the session's client id, the event name and its `data`.
+    pub fn on_event(
+        mut self,
+        on_event: impl Fn(u64, &str, &Value) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_event = Arc::new(Some(Box::new(on_event)));
+        self
+    }
+
+    /// Adds and connects a session for `client_id`, reporting `pid` as the
+    /// process the presence belongs to. Adding an existing client id does
+    /// nothing.
+    ///
+    /// Fails if Discord rejects the client id, unless in deferred mode.
+    pub fn add(&mut self, client_id: u64, pid: u32) -> io::Result<()> {
+        if self.session(client_id).is_some() {
+            return Ok(());
+        }
+
+        let on_event = Arc::clone(&self.on_event);
+        let mut client = RichClient::new(client_id)
+            .socket_policy(self.socket_policy)
+            .deferred(self.deferred)
+            .clock(Arc::clone(&self.clock))
+            .on_event(move |evt, data| {
+                if let Some(on_event) = on_event.as_ref() {
+                    on_event(client_id, evt, data);
+                }
+            });
+        client.pid = pid;
+        client.discovery = Some(Arc::clone(&self.discovery));
+        client.connect(true)?;
+        self.sessions.push(client);
+
+        Ok(())
+    }
+
+    /// Clears and disconnects the session for `client_id`.
+    pub fn remove(&mut self, client_id: u64) -> io::Result<()> {
+        let Some(index) = self
+            .sessions
+            .iter()
+            .position(|session| session.client_id == client_id)
+        else {
+            return Ok(());
+        };
+
+        if self.active == Some(client_id) {
+            self.active = None;
+        }
+        let mut session = self.sessions.remove(index);
+        let _ = session.clear();
+        session.shutdown()
+    }
+
+    /// Makes `client_id` the active application, clearing the presence of
+    /// the previously active one. If clearing it fails, the previous one
+    /// stays active.
+    pub fn switch(&mut self, client_id: u64) -> io::Result<()> {
+        if self.session(client_id).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No session for client id {client_id}"),
+            ));
+        }
+        if self.active == Some(client_id) {
+            return Ok(());
+        }
+
+        if let Some(previous) = self.active {
+            self.connected_session(previous)?.clear()?;
+        }
+        self.active = Some(client_id);
+
+        Ok(())
+    }
+
+    /// The client id of the active application.
+    pub fn active(&self) -> Option<u64> {
+        self.active
+    }
+
+    /// Updates the presence of the active application.
+    pub fn update(&mut self, activity: Activity<'a>) -> io::Result<()> {
+        self.active_session()?.update(activity)
+    }
+
+    /// Clears the presence of the active application.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.active_session()?.clear()
+    }
+
+    /// The session for `client_id`, for commands other than presence
+    /// updates.
+    pub fn session(&mut self, client_id: u64) -> Option<&mut RichClient<'a>> {
+        self.sessions
+            .iter_mut()
+            .find(|session| session.client_id == client_id)
+    }
+
+    fn active_session(&mut self) -> io::Result<&mut RichClient<'a>> {
+        let active = self.active.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "No active session")
+        })?;
+        self.connected_session(active)
+    }
+
+    /// The session for `client_id`, reconnecting it first if it lost its
+    /// connection. Sessions in deferred mode reconnect on their own.
+    fn connected_session(
+        &mut self,
+        client_id: u64,
+    ) -> io::Result<&mut RichClient<'a>> {
+        let deferred = self.deferred;
+        let session = self.session(client_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No session for client id {client_id}"),
+            )
+        })?;
+        if !deferred
+            && *session.connection_state.read().unwrap()
+                == ConnectionState::Disconnected
+        {
+            session.connect(true)?;
+        }
+
+        Ok(session)
+    }
+}
+
+/// Discovery shared by the sessions of a [`PresenceManager`].
+#[derive(Default)]
+pub(crate) struct SharedDiscovery {
+    /// The kind and path of the socket the sessions connect to, once one
+    /// was found.
+    target: Mutex<Option<(ClientKind, String)>>,
+    /// The background connections of sessions in deferred mode, woken up
+    /// when a socket is found.
+    waiting: Mutex<Vec<Weak<Deferred>>>,
+}
+
+impl SharedDiscovery {
+    /// Connects to the socket the other sessions use, or looks for a new
+    /// one if it is gone or none was found yet.
+    pub(crate) fn open(
+        &self,
+        policy: SocketPolicy,
+    ) -> io::Result<(Pipe, Target)> {
+        let mut shared = self.target.lock().unwrap();
+        if let Some((kind, path)) = shared.as_ref() {
+            if let Ok(pipe) = open_path(path, policy) {
+                let target = Target {
+                    kind: *kind,
+                    path: path.clone(),
+                    skipped: Vec::new(),
+                };
+                return Ok((pipe, target));
+            }
+        }
+
+        let (pipe, target) = open_pipe(policy)?;
+        *shared = Some((target.kind, target.path.clone()));
+        drop(shared);
+        for deferred in self.waiting.lock().unwrap().iter() {
+            if let Some(deferred) = deferred.upgrade() {
+                deferred.retry();
+            }
+        }
+
+        Ok((pipe, target))
+    }
+
+    /// Wakes up `deferred` whenever a session finds a new socket.
+    pub(crate) fn register(&self, deferred: &Arc<Deferred>) {
+        let mut waiting = self.waiting.lock().unwrap();
+        waiting.retain(|deferred| deferred.strong_count() > 0);
+        waiting.push(Arc::downgrade(deferred));
+    }
+}
+
+/// Connects through `discovery` if the client belongs to a
+/// [`PresenceManager`], or looks for a socket on its own otherwise.
+pub(crate) fn discover(
+    policy: SocketPolicy,
+    discovery: Option<&SharedDiscovery>,
+) -> io::Result<(Pipe, Target)> {
+    match discovery {
+        Some(discovery) => discovery.open(policy),
+        None => open_pipe(policy),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(target_os = "windows"))]
+    use std::os::unix::net::UnixStream;
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn failed_clear_keeps_previous_session_active() {
+        let mut manager = PresenceManager::new();
+        let mut discord = Vec::new();
+        for client_id in [1, 2] {
+            let client = RichClient::new(client_id);
+            let (peer, pipe) = UnixStream::pair().unwrap();
+            *client.pipe.write().unwrap() = Some(pipe);
+            *client.connection_state.write().unwrap() =
+                ConnectionState::SentHandshake;
+            manager.sessions.push(client);
+            discord.push(peer);
+        }
+        manager.switch(1).unwrap();
+
+        drop(discord.remove(0));
+        assert!(manager.switch(2).is_err());
+        assert_eq!(manager.active(), Some(1));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::rpc::activity::{Activity, ActivityFlags, ActivityType};
+use crate::rpc::timestamp::Timestamp;
+
+/// The activity Discord stored in response to `SET_ACTIVITY`.
+///
+/// Discord normalizes what it receives: asset keys are resolved to asset
+/// ids, long strings are truncated and unknown fields are dropped. Use
+/// [`AckedActivity::changes`] to compare it with what was sent.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AckedActivity {
+    pub application_id: Option<String>,
+    pub name: Option<String>,
+    pub ty: Option<ActivityType>,
+    pub details: Option<String>,
+    pub details_url: Option<String>,
+    pub state: Option<String>,
+    pub state_url: Option<String>,
+    pub start: Option<Timestamp>,
+    pub end: Option<Timestamp>,
+    pub large_image: Option<String>,
+    pub large_text: Option<String>,
+    pub large_url: Option<String>,
+    pub small_image: Option<String>,
+    pub small_text: Option<String>,
+    pub small_url: Option<String>,
+    pub party_id: Option<String>,
+    pub party_size: Option<[u64; 2]>,
+    /// Discord only echoes the button labels.
+    pub buttons: Vec<String>,
+    pub instance: Option<bool>,
+    pub flags: Option<ActivityFlags>,
+}
+
+/// A field that Discord stored differently from how it was sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub sent: Option<String>,
+    /// `None` if Discord dropped the field.
+    pub acked: Option<String>,
+}
+
+impl AckedActivity {
+    /// Lists the fields of `sent` that Discord dropped or rewrote.
+    pub fn changes(&self, sent: &Activity) -> Vec<FieldChange> {
+        let assets = sent.assets.clone().unwrap_or_default();
+        let timestamps = sent.timestamps.clone().unwrap_or_default();
+        let party = sent.party.clone().unwrap_or_default();
+        let millis = |time: Option<Timestamp>| {
+            time.map(|time| time.as_millis().to_string())
+        };
+        let size = |size: Option<[u64; 2]>| {
+            size.map(|size| format!("{}/{}", size[0], size[1]))
+        };
+
+        let fields = [
+            ("details", sent.details.map(str::to_string), &self.details),
+            (
+                "details_url",
+                sent.details_url.map(str::to_string),
+                &self.details_url,
+            ),
+            ("state", sent.state.map(str::to_string), &self.state),
+            (
+                "state_url",
+                sent.state_url.map(str::to_string),
+                &self.state_url,
+            ),
+            (
+                "large_image",
+                assets.large_image.map(str::to_string),
+                &self.large_image,
+            ),
+            (
+                "large_text",
+                assets.large_text.map(str::to_string),
+                &self.large_text,
+            ),
+            (
+                "large_url",
+                assets.large_url.map(str::to_string),
+                &self.large_url,
+            ),
+            (
+                "small_image",
+                assets.small_image.map(str::to_string),
+                &self.small_image,
+            ),
+            (
+                "small_text",
+                assets.small_text.map(str::to_string),
+                &self.small_text,
+            ),
+            (
+                "small_url",
+                assets.small_url.map(str::to_string),
+                &self.small_url,
+            ),
+            ("party_id", party.id.map(str::to_string), &self.party_id),
+        ];
+
+        let mut changes: Vec<FieldChange> = fields
+            .into_iter()
+            .filter(|(_, sent, _)| sent.is_some())
+            .filter(|(_, sent, acked)| sent != *acked)
+            .map(|(field, sent, acked)| FieldChange {
+                field,
+                sent,
+                acked: acked.clone(),
+            })
+            .collect();
+
+        for (field, sent, acked) in [
+            ("start", millis(timestamps.start), millis(self.start)),
+            ("end", millis(timestamps.end), millis(self.end)),
+            (
+                "party_size",
+                size(party.size.map(|[current, max]| {
+                    [u64::from(current), u64::from(max)]
+                })),
+                size(self.party_size),
+            ),
+        ] {
+            if sent.is_some() && sent != acked {
+                changes.push(FieldChange { field, sent, acked });
+            }
+        }
+
+        if let Some(buttons) = sent.buttons {
+            let labels: Vec<&str> =
+                buttons.iter().map(|button| button.label).collect();
+            if labels != self.buttons {
+                changes.push(FieldChange {
+                    field: "buttons",
+                    sent: Some(labels.join(", ")),
+                    acked: (!self.buttons.is_empty())
+                        .then(|| self.buttons.join(", ")),
+                });
+            }
+        }
+
+        changes
+    }
+}
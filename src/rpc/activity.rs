@@ -1,9 +1,16 @@
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::rpc::timestamp::Timestamp;
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ActivityAssets<'a> {
     pub(crate) large_image: Option<&'a str>,
     pub(crate) large_text: Option<&'a str>,
+    pub(crate) large_url: Option<&'a str>,
     pub(crate) small_image: Option<&'a str>,
     pub(crate) small_text: Option<&'a str>,
+    pub(crate) small_url: Option<&'a str>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -15,14 +22,19 @@ pub struct ActivityButton<'a> {
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Activity<'a> {
     pub(crate) ty: ActivityType,
+    pub(crate) name: Option<&'a str>,
+    pub(crate) status_display_type: Option<StatusDisplayType>,
     pub(crate) details: Option<&'a str>,
+    pub(crate) details_url: Option<&'a str>,
     pub(crate) state: Option<&'a str>,
+    pub(crate) state_url: Option<&'a str>,
     pub(crate) assets: Option<ActivityAssets<'a>>,
     pub(crate) buttons: Option<&'a [ActivityButton<'a>]>,
     pub(crate) timestamps: Option<ActivityTimestamps>,
     pub(crate) party: Option<ActivityParty<'a>>,
     pub(crate) secrets: Option<ActivitySecrets<'a>>,
     pub(crate) instance: Option<bool>,
+    pub(crate) flags: Option<ActivityFlags>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -31,6 +43,7 @@ pub enum ActivityType {
     Playing,
     Listening,
     Watching,
+    Competing,
 }
 
 impl ActivityType {
@@ -39,14 +52,76 @@ impl ActivityType {
             ActivityType::Playing => 0,
             ActivityType::Listening => 2,
             ActivityType::Watching => 3,
+            ActivityType::Competing => 5,
         }
     }
 }
 
+/// Which field is shown next to the user's name in the member list.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum StatusDisplayType {
+    #[default]
+    Name,
+    State,
+    Details,
+}
+
+impl StatusDisplayType {
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            StatusDisplayType::Name => 0,
+            StatusDisplayType::State => 1,
+            StatusDisplayType::Details => 2,
+        }
+    }
+}
+
+/// Activity flags, combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ActivityFlags(u32);
+
+impl ActivityFlags {
+    pub const INSTANCE: Self = Self(1 << 0);
+    pub const JOIN: Self = Self(1 << 1);
+    pub const SPECTATE: Self = Self(1 << 2);
+    pub const JOIN_REQUEST: Self = Self(1 << 3);
+    pub const SYNC: Self = Self(1 << 4);
+    pub const PLAY: Self = Self(1 << 5);
+    pub const PARTY_PRIVACY_FRIENDS: Self = Self(1 << 6);
+    pub const PARTY_PRIVACY_VOICE_CHANNEL: Self = Self(1 << 7);
+    pub const EMBEDDED: Self = Self(1 << 8);
+
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl std::ops::BitOr for ActivityFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for ActivityFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ActivityTimestamps {
-    pub(crate) start: Option<u128>,
-    pub(crate) end: Option<u128>,
+    pub(crate) start: Option<Timestamp>,
+    pub(crate) end: Option<Timestamp>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -67,16 +142,42 @@ impl<'a> Activity<'a> {
         Self::default()
     }
 
+    /// Overrides the application name shown for the activity.
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn status_display_type(
+        mut self,
+        status_display_type: StatusDisplayType,
+    ) -> Self {
+        self.status_display_type = Some(status_display_type);
+        self
+    }
+
     pub fn details(mut self, details: &'a str) -> Self {
         self.details = Some(details);
         self
     }
 
+    /// Makes the details line a link to `url`.
+    pub fn details_url(mut self, url: &'a str) -> Self {
+        self.details_url = Some(url);
+        self
+    }
+
     pub fn state(mut self, state: &'a str) -> Self {
         self.state = Some(state);
         self
     }
 
+    /// Makes the state line a link to `url`.
+    pub fn state_url(mut self, url: &'a str) -> Self {
+        self.state_url = Some(url);
+        self
+    }
+
     pub fn assets(mut self, assets: ActivityAssets<'a>) -> Self {
         self.assets = Some(assets);
         self
@@ -92,20 +193,57 @@ impl<'a> Activity<'a> {
         self
     }
 
-    pub fn start_time(mut self, time: u128) -> Self {
+    pub fn start_time(mut self, time: impl Into<Timestamp>) -> Self {
         self.timestamps
             .get_or_insert_with(ActivityTimestamps::default)
-            .start = Some(time);
+            .start = Some(time.into());
         self
     }
 
-    pub fn end_time(mut self, time: u128) -> Self {
+    pub fn end_time(mut self, time: impl Into<Timestamp>) -> Self {
         self.timestamps
             .get_or_insert_with(ActivityTimestamps::default)
-            .end = Some(time);
+            .end = Some(time.into());
         self
     }
 
+    /// Shows the time elapsed since `since`.
+    ///
+    /// An [`Instant`](std::time::Instant) is placed by the system clock;
+    /// use [`Timestamp::from_instant`] to place it by another.
+    pub fn elapsed_since(self, since: impl Into<Timestamp>) -> Self {
+        self.timestamps(ActivityTimestamps::with_start(since))
+    }
+
+    /// Shows a countdown that ends `remaining` from now.
+    pub fn remaining(self, remaining: Duration) -> Self {
+        self.remaining_on(&SystemClock, remaining)
+    }
+
+    /// Like [`Activity::remaining`], counting from the time of `clock`.
+    pub fn remaining_on(self, clock: &dyn Clock, remaining: Duration) -> Self {
+        self.timestamps(ActivityTimestamps::with_end(
+            clock.timestamp() + remaining,
+        ))
+    }
+
+    /// Shows a progress bar for media that is `position` into a total of
+    /// `duration`.
+    pub fn progress(self, position: Duration, duration: Duration) -> Self {
+        self.progress_on(&SystemClock, position, duration)
+    }
+
+    /// Like [`Activity::progress`], counting from the time of `clock`.
+    pub fn progress_on(
+        self,
+        clock: &dyn Clock,
+        position: Duration,
+        duration: Duration,
+    ) -> Self {
+        let start = clock.timestamp() - position;
+        self.timestamps(ActivityTimestamps::new(start, start + duration))
+    }
+
     pub fn party(mut self, party: ActivityParty<'a>) -> Self {
         self.party = Some(party);
         self
@@ -152,6 +290,11 @@ impl<'a> Activity<'a> {
         self
     }
 
+    pub fn flags(mut self, flags: ActivityFlags) -> Self {
+        self.flags = Some(flags);
+        self
+    }
+
     pub fn ty(mut self, ty: ActivityType) -> Self {
         self.ty = ty;
         self
@@ -171,6 +314,13 @@ impl<'a> Activity<'a> {
         self
     }
 
+    pub fn large_url(mut self, large_url: &'a str) -> Self {
+        self.assets
+            .get_or_insert_with(ActivityAssets::default)
+            .large_url = Some(large_url);
+        self
+    }
+
     pub fn small_image(mut self, small_image: &'a str) -> Self {
         self.assets
             .get_or_insert_with(ActivityAssets::default)
@@ -184,6 +334,13 @@ impl<'a> Activity<'a> {
             .small_text = Some(small_text);
         self
     }
+
+    pub fn small_url(mut self, small_url: &'a str) -> Self {
+        self.assets
+            .get_or_insert_with(ActivityAssets::default)
+            .small_url = Some(small_url);
+        self
+    }
 }
 
 impl<'a> ActivityAssets<'a> {
@@ -201,6 +358,11 @@ impl<'a> ActivityAssets<'a> {
         self
     }
 
+    pub fn large_url(mut self, large_url: &'a str) -> Self {
+        self.large_url = Some(large_url);
+        self
+    }
+
     pub fn small_image(mut self, small_image: &'a str) -> Self {
         self.small_image = Some(small_image);
         self
@@ -210,6 +372,11 @@ impl<'a> ActivityAssets<'a> {
         self.small_text = Some(small_text);
         self
     }
+
+    pub fn small_url(mut self, small_url: &'a str) -> Self {
+        self.small_url = Some(small_url);
+        self
+    }
 }
 
 impl<'a> ActivityButton<'a> {
@@ -238,17 +405,24 @@ impl<'a> ActivitySecrets<'a> {
 }
 
 impl ActivityTimestamps {
-    pub fn with_start(start: u128) -> Self {
+    pub fn new(start: impl Into<Timestamp>, end: impl Into<Timestamp>) -> Self {
+        Self {
+            start: Some(start.into()),
+            end: Some(end.into()),
+        }
+    }
+
+    pub fn with_start(start: impl Into<Timestamp>) -> Self {
         Self {
-            start: Some(start),
+            start: Some(start.into()),
             end: None,
         }
     }
 
-    pub fn with_end(end: u128) -> Self {
+    pub fn with_end(end: impl Into<Timestamp>) -> Self {
         Self {
             start: None,
-            end: Some(end),
+            end: Some(end.into()),
         }
     }
 }
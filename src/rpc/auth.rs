@@ -0,0 +1,85 @@
+use crate::rpc::user::User;
+
+/// An OAuth2 scope that can be requested with
+/// [`RichClient::authorize`](crate::RichClient::authorize).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Identify,
+    Email,
+    Connections,
+    Guilds,
+    GuildsJoin,
+    GuildsMembersRead,
+    MessagesRead,
+    Rpc,
+    RpcActivitiesWrite,
+    RpcNotificationsRead,
+    RpcVoiceRead,
+    RpcVoiceWrite,
+    RpcVideoRead,
+    RpcVideoWrite,
+    RpcScreenshareRead,
+    RpcScreenshareWrite,
+    ActivitiesRead,
+    ActivitiesWrite,
+    RelationshipsRead,
+    ApplicationsCommands,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Identify => "identify",
+            Scope::Email => "email",
+            Scope::Connections => "connections",
+            Scope::Guilds => "guilds",
+            Scope::GuildsJoin => "guilds.join",
+            Scope::GuildsMembersRead => "guilds.members.read",
+            Scope::MessagesRead => "messages.read",
+            Scope::Rpc => "rpc",
+            Scope::RpcActivitiesWrite => "rpc.activities.write",
+            Scope::RpcNotificationsRead => "rpc.notifications.read",
+            Scope::RpcVoiceRead => "rpc.voice.read",
+            Scope::RpcVoiceWrite => "rpc.voice.write",
+            Scope::RpcVideoRead => "rpc.video.read",
+            Scope::RpcVideoWrite => "rpc.video.write",
+            Scope::RpcScreenshareRead => "rpc.screenshare.read",
+            Scope::RpcScreenshareWrite => "rpc.screenshare.write",
+            Scope::ActivitiesRead => "activities.read",
+            Scope::ActivitiesWrite => "activities.write",
+            Scope::RelationshipsRead => "relationships.read",
+            Scope::ApplicationsCommands => "applications.commands",
+        }
+    }
+}
+
+/// The authorization code returned by `AUTHORIZE` once the user has
+/// accepted the consent dialog.
+///
+/// It has to be exchanged for an access token through Discord's OAuth2
+/// token endpoint, which requires the application's client secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthCode {
+    pub code: String,
+}
+
+/// The application an access token was issued to.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Application {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub rpc_origins: Vec<String>,
+}
+
+/// The result of a successful `AUTHENTICATE`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AuthenticatedSession {
+    pub application: Application,
+    pub user: User,
+    /// The scopes granted to the access token, as sent by Discord.
+    pub scopes: Vec<String>,
+    /// When the access token expires, as an ISO 8601 timestamp.
+    pub expires: String,
+}
@@ -0,0 +1,105 @@
+use crate::rpc::ack::AckedActivity;
+use crate::rpc::activity::Activity;
+use crate::rpc::auth::{AuthCode, AuthenticatedSession, Scope};
+use crate::rpc::event::Event;
+use crate::rpc::guild::{Channel, Guild};
+use crate::rpc::voice::{VoiceChannel, VoiceSettings};
+
+/// A command understood by Discord's local RPC server.
+///
+/// Commands other than [`Command::SetActivity`] and the activity invite
+/// commands require an authenticated connection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command<'a> {
+    SetActivity {
+        pid: u32,
+        activity: Option<&'a Activity<'a>>,
+    },
+    Subscribe(Event<'a>),
+    Unsubscribe(Event<'a>),
+    Authorize {
+        client_id: u64,
+        scopes: &'a [Scope],
+        rpc_token: Option<&'a str>,
+        username: Option<&'a str>,
+    },
+    Authenticate {
+        access_token: &'a str,
+    },
+    GetGuild {
+        guild_id: &'a str,
+        timeout: Option<u32>,
+    },
+    GetGuilds,
+    GetChannel {
+        channel_id: &'a str,
+    },
+    GetChannels {
+        guild_id: &'a str,
+    },
+    SelectVoiceChannel {
+        channel_id: Option<&'a str>,
+        timeout: Option<u32>,
+        force: bool,
+    },
+    GetSelectedVoiceChannel,
+    GetVoiceSettings,
+    SetVoiceSettings(&'a VoiceSettings),
+    SendActivityJoinInvite {
+        user_id: &'a str,
+    },
+    CloseActivityRequest {
+        user_id: &'a str,
+    },
+}
+
+impl Command<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::SetActivity { .. } => "SET_ACTIVITY",
+            Command::Subscribe(_) => "SUBSCRIBE",
+            Command::Unsubscribe(_) => "UNSUBSCRIBE",
+            Command::Authorize { .. } => "AUTHORIZE",
+            Command::Authenticate { .. } => "AUTHENTICATE",
+            Command::GetGuild { .. } => "GET_GUILD",
+            Command::GetGuilds => "GET_GUILDS",
+            Command::GetChannel { .. } => "GET_CHANNEL",
+            Command::GetChannels { .. } => "GET_CHANNELS",
+            Command::SelectVoiceChannel { .. } => "SELECT_VOICE_CHANNEL",
+            Command::GetSelectedVoiceChannel => "GET_SELECTED_VOICE_CHANNEL",
+            Command::GetVoiceSettings => "GET_VOICE_SETTINGS",
+            Command::SetVoiceSettings(_) => "SET_VOICE_SETTINGS",
+            Command::SendActivityJoinInvite { .. } => {
+                "SEND_ACTIVITY_JOIN_INVITE"
+            }
+            Command::CloseActivityRequest { .. } => "CLOSE_ACTIVITY_REQUEST",
+        }
+    }
+}
+
+/// The typed `data` of Discord's response to a [`Command`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// `None` when the activity was cleared.
+    SetActivity(Option<Box<AckedActivity>>),
+    Subscribe {
+        evt: String,
+    },
+    Unsubscribe {
+        evt: String,
+    },
+    Authorize(AuthCode),
+    Authenticate(Box<AuthenticatedSession>),
+    GetGuild(Guild),
+    GetGuilds(Vec<Guild>),
+    GetChannel(Channel),
+    GetChannels(Vec<Channel>),
+    /// `None` when leaving voice.
+    SelectVoiceChannel(Option<VoiceChannel>),
+    /// `None` when not in a voice channel.
+    GetSelectedVoiceChannel(Option<VoiceChannel>),
+    GetVoiceSettings(VoiceSettings),
+    SetVoiceSettings(VoiceSettings),
+    SendActivityJoinInvite,
+    CloseActivityRequest,
+}
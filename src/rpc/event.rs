@@ -0,0 +1,85 @@
+use std::io;
+
+use crate::json::Value;
+use crate::rpc::guild::{Channel, Guild};
+use crate::rpc::voice::{VoiceConnectionStatus, VoiceSettings, VoiceState};
+
+/// An event that can be subscribed to with
+/// [`Command::Subscribe`](super::command::Command::Subscribe).
+///
+/// Dispatched events are delivered to the callback registered with
+/// [`RichClient::on_event`](crate::RichClient::on_event) and can be turned
+/// into a [`Dispatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    ActivityJoin,
+    ActivitySpectate,
+    ActivityJoinRequest,
+    VoiceChannelSelect,
+    VoiceSettingsUpdate,
+    VoiceStateCreate { channel_id: &'a str },
+    VoiceStateUpdate { channel_id: &'a str },
+    VoiceStateDelete { channel_id: &'a str },
+    SpeakingStart { channel_id: &'a str },
+    SpeakingStop { channel_id: &'a str },
+    VoiceConnectionStatus,
+    GuildStatus { guild_id: &'a str },
+    ChannelCreate,
+}
+
+impl Event<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::ActivityJoin => "ACTIVITY_JOIN",
+            Event::ActivitySpectate => "ACTIVITY_SPECTATE",
+            Event::ActivityJoinRequest => "ACTIVITY_JOIN_REQUEST",
+            Event::VoiceChannelSelect => "VOICE_CHANNEL_SELECT",
+            Event::VoiceSettingsUpdate => "VOICE_SETTINGS_UPDATE",
+            Event::VoiceStateCreate { .. } => "VOICE_STATE_CREATE",
+            Event::VoiceStateUpdate { .. } => "VOICE_STATE_UPDATE",
+            Event::VoiceStateDelete { .. } => "VOICE_STATE_DELETE",
+            Event::SpeakingStart { .. } => "SPEAKING_START",
+            Event::SpeakingStop { .. } => "SPEAKING_STOP",
+            Event::VoiceConnectionStatus => "VOICE_CONNECTION_STATUS",
+            Event::GuildStatus { .. } => "GUILD_STATUS",
+            Event::ChannelCreate => "CHANNEL_CREATE",
+        }
+    }
+}
+
+/// The typed payload of a dispatched event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dispatch {
+    VoiceChannelSelect {
+        channel_id: Option<String>,
+        guild_id: Option<String>,
+    },
+    VoiceSettingsUpdate(VoiceSettings),
+    VoiceStateCreate(VoiceState),
+    VoiceStateUpdate(VoiceState),
+    VoiceStateDelete(VoiceState),
+    SpeakingStart {
+        user_id: String,
+    },
+    SpeakingStop {
+        user_id: String,
+    },
+    VoiceConnectionStatus(VoiceConnectionStatus),
+    GuildStatus {
+        guild: Guild,
+        online: Option<u64>,
+    },
+    ChannelCreate(Channel),
+    /// Any event without a typed payload.
+    Other {
+        evt: String,
+        data: Value,
+    },
+}
+
+impl Dispatch {
+    /// Interprets the name and `data` passed to the `on_event` callback.
+    pub fn parse(evt: &str, data: &Value) -> io::Result<Self> {
+        Self::from_value(evt, data)
+    }
+}
@@ -0,0 +1,75 @@
+use crate::rpc::voice::VoiceState;
+
+/// A guild the user is a member of.
+///
+/// Discord leaves out the type and voice states in some responses, such
+/// as the guilds listed by `GET_GUILDS`. Voice states per channel are
+/// listed on [`Channel`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Guild {
+    pub id: String,
+    pub name: String,
+    pub icon_url: Option<String>,
+    pub ty: Option<u64>,
+    pub voice_states: Option<Vec<VoiceState>>,
+}
+
+/// A guild or private channel.
+///
+/// Channels listed by `GET_CHANNELS` only carry their id, name and type.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Channel {
+    pub id: String,
+    pub name: String,
+    pub ty: ChannelType,
+    pub guild_id: Option<String>,
+    pub topic: Option<String>,
+    pub bitrate: Option<u64>,
+    pub user_limit: Option<u64>,
+    pub position: Option<u64>,
+    pub voice_states: Vec<VoiceState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelType {
+    #[default]
+    GuildText,
+    Dm,
+    GuildVoice,
+    GroupDm,
+    GuildCategory,
+    GuildAnnouncement,
+    GuildStageVoice,
+    GuildForum,
+    Other(u64),
+}
+
+impl ChannelType {
+    pub fn from_u64(ty: u64) -> Self {
+        match ty {
+            0 => ChannelType::GuildText,
+            1 => ChannelType::Dm,
+            2 => ChannelType::GuildVoice,
+            3 => ChannelType::GroupDm,
+            4 => ChannelType::GuildCategory,
+            5 => ChannelType::GuildAnnouncement,
+            13 => ChannelType::GuildStageVoice,
+            15 => ChannelType::GuildForum,
+            ty => ChannelType::Other(ty),
+        }
+    }
+
+    pub fn to_u64(&self) -> u64 {
+        match self {
+            ChannelType::GuildText => 0,
+            ChannelType::Dm => 1,
+            ChannelType::GuildVoice => 2,
+            ChannelType::GroupDm => 3,
+            ChannelType::GuildCategory => 4,
+            ChannelType::GuildAnnouncement => 5,
+            ChannelType::GuildStageVoice => 13,
+            ChannelType::GuildForum => 15,
+            ChannelType::Other(ty) => *ty,
+        }
+    }
+}
@@ -1,2 +1,9 @@
+pub mod ack;
 pub mod activity;
-pub mod packet;
+pub mod auth;
+pub mod command;
+pub mod event;
+pub mod guild;
+pub mod timestamp;
+pub mod user;
+pub mod voice;
@@ -0,0 +1,81 @@
+use std::ops::{Add, Sub};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::clock::{Clock, SystemClock};
+
+/// A point in time, as sent to Discord: milliseconds since the Unix epoch.
+///
+/// Build one from a [`SystemTime`] or [`Instant`] rather than from a raw
+/// number where possible; [`Timestamp::from_secs`] and
+/// [`Timestamp::from_millis`] make the unit explicit otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    /// The current time by the system clock. Use [`Clock::timestamp`] to
+    /// read it from another clock.
+    pub fn now() -> Self {
+        SystemClock.timestamp()
+    }
+
+    /// Maps `instant` onto the wall clock by its distance from the current
+    /// time of `clock`.
+    pub fn from_instant(instant: Instant, clock: &dyn Clock) -> Self {
+        let now = clock.now();
+        if instant <= now {
+            clock.timestamp() - (now - instant)
+        } else {
+            clock.timestamp() + (instant - now)
+        }
+    }
+
+    pub fn from_secs(secs: u64) -> Self {
+        Self(secs.saturating_mul(1000))
+    }
+
+    pub fn from_millis(millis: u64) -> Self {
+        Self(millis)
+    }
+
+    pub fn as_secs(&self) -> u64 {
+        self.0 / 1000
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        Self(
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+        )
+    }
+}
+
+impl From<Instant> for Timestamp {
+    /// Maps the instant onto the wall clock by the system clock. See
+    /// [`Timestamp::from_instant`].
+    fn from(instant: Instant) -> Self {
+        Self::from_instant(instant, &SystemClock)
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Self;
+
+    fn add(self, rhs: Duration) -> Self {
+        Self(self.0.saturating_add(rhs.as_millis() as u64))
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Self;
+
+    fn sub(self, rhs: Duration) -> Self {
+        Self(self.0.saturating_sub(rhs.as_millis() as u64))
+    }
+}
@@ -0,0 +1,22 @@
+/// A Discord user, as returned by the RPC server.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub discriminator: Option<String>,
+    pub global_name: Option<String>,
+    pub avatar: Option<String>,
+    pub bot: bool,
+}
+
+impl User {
+    /// The URL of the user's avatar, if they have set one.
+    pub fn avatar_url(&self) -> Option<String> {
+        self.avatar.as_ref().map(|avatar| {
+            format!(
+                "https://cdn.discordapp.com/avatars/{}/{}.png",
+                self.id, avatar
+            )
+        })
+    }
+}
@@ -0,0 +1,132 @@
+use crate::rpc::guild::Channel;
+use crate::rpc::user::User;
+
+/// The user's voice settings.
+///
+/// Returned in full by `GET_VOICE_SETTINGS`. When passed to
+/// [`RichClient::set_voice_settings`](crate::RichClient::set_voice_settings),
+/// only the fields that are set are changed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VoiceSettings {
+    pub input: Option<VoiceIo>,
+    pub output: Option<VoiceIo>,
+    pub mode: Option<VoiceMode>,
+    pub automatic_gain_control: Option<bool>,
+    pub echo_cancellation: Option<bool>,
+    pub noise_suppression: Option<bool>,
+    pub qos: Option<bool>,
+    pub silence_warning: Option<bool>,
+    pub deaf: Option<bool>,
+    pub mute: Option<bool>,
+}
+
+/// Input or output device settings.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VoiceIo {
+    pub device_id: Option<String>,
+    /// Input volume ranges from 0 to 100, output volume from 0 to 200.
+    pub volume: Option<f64>,
+    /// Only present in responses.
+    pub available_devices: Vec<AudioDevice>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AudioDevice {
+    pub id: String,
+    pub name: String,
+}
+
+/// Voice activity detection settings.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VoiceMode {
+    /// `VOICE_ACTIVITY` or `PUSH_TO_TALK`.
+    pub ty: Option<String>,
+    pub auto_threshold: Option<bool>,
+    /// Threshold in dB, from -100 to 0.
+    pub threshold: Option<f64>,
+    /// Push-to-talk release delay in milliseconds.
+    pub delay: Option<f64>,
+}
+
+/// A voice channel, as returned by `GET_SELECTED_VOICE_CHANNEL` and
+/// `SELECT_VOICE_CHANNEL`.
+pub type VoiceChannel = Channel;
+
+/// A user's state in a voice channel.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VoiceState {
+    pub user: User,
+    pub nick: Option<String>,
+    /// The local volume of the user, from 0 to 200.
+    pub volume: Option<f64>,
+    /// Whether the user has been muted locally.
+    pub muted_locally: bool,
+    pub mute: bool,
+    pub deaf: bool,
+    pub self_mute: bool,
+    pub self_deaf: bool,
+    pub suppress: bool,
+}
+
+/// The state of the voice connection, sent with `VOICE_CONNECTION_STATUS`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VoiceConnectionStatus {
+    /// For example `VOICE_CONNECTED` or `DISCONNECTED`.
+    pub state: String,
+    pub hostname: Option<String>,
+    pub average_ping: Option<f64>,
+    pub last_ping: Option<f64>,
+}
+
+impl VoiceSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn deaf(mut self, deaf: bool) -> Self {
+        self.deaf = Some(deaf);
+        self
+    }
+
+    pub fn mute(mut self, mute: bool) -> Self {
+        self.mute = Some(mute);
+        self
+    }
+
+    pub fn input_volume(mut self, volume: f64) -> Self {
+        self.input.get_or_insert_with(VoiceIo::default).volume = Some(volume);
+        self
+    }
+
+    pub fn output_volume(mut self, volume: f64) -> Self {
+        self.output.get_or_insert_with(VoiceIo::default).volume = Some(volume);
+        self
+    }
+
+    pub fn input_device(mut self, device_id: &str) -> Self {
+        self.input.get_or_insert_with(VoiceIo::default).device_id =
+            Some(device_id.to_string());
+        self
+    }
+
+    pub fn output_device(mut self, device_id: &str) -> Self {
+        self.output.get_or_insert_with(VoiceIo::default).device_id =
+            Some(device_id.to_string());
+        self
+    }
+
+    pub fn automatic_gain_control(mut self, enabled: bool) -> Self {
+        self.automatic_gain_control = Some(enabled);
+        self
+    }
+
+    pub fn echo_cancellation(mut self, enabled: bool) -> Self {
+        self.echo_cancellation = Some(enabled);
+        self
+    }
+
+    pub fn noise_suppression(mut self, enabled: bool) -> Self {
+        self.noise_suppression = Some(enabled);
+        self
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::clock::{Clock, SystemClock};
+use crate::rpc::{activity::Activity, timestamp::Timestamp};
+
+/// Variables a scene's template is built from.
+pub type Vars = HashMap<String, String>;
+
+/// Builds the activity of a scene from its variables.
+type Template = Box<dyn for<'v> Fn(&'v Vars) -> Activity<'v> + Send + Sync>;
+
+/// What happens to a scene's timer when the scene is entered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerRule {
+    /// Restart the timer from zero.
+    Reset,
+    /// Keep counting from when the timer first started, including the time
+    /// spent in other scenes.
+    Keep,
+    /// Resume the timer, excluding the time spent in scenes that don't
+    /// use it.
+    Pause,
+}
+
+struct Scene {
+    template: Template,
+    timer: Option<(String, TimerRule)>,
+}
+
+#[derive(Clone, Copy)]
+struct Timer {
+    started: Timestamp,
+    /// Time spent in scenes using this timer.
+    elapsed: Duration,
+}
+
+/// A set of named scenes, such as a menu, a lobby or a match, each with
+/// its own activity template and optionally a timer shown as the elapsed
+/// time.
+///
+/// Timers are shared by name, so a match timer can survive a pause menu
+/// that doesn't show it. Register the scenes with a
+/// [`RichClientHandle`](crate::RichClientHandle) and switch between them
+/// with [`RichClientHandle::enter_scene`](crate::RichClientHandle::enter_scene).
+/// Timers then follow the handle's [`Clock`].
+pub struct Scenes {
+    scenes: HashMap<String, Scene>,
+    timers: HashMap<String, Timer>,
+    /// The current scene, when it was entered and the start it shows.
+    current: Option<(String, Instant, Option<Timestamp>)>,
+    pub(crate) clock: Arc<dyn Clock>,
+}
+
+impl Default for Scenes {
+    fn default() -> Self {
+        Self {
+            scenes: HashMap::new(),
+            timers: HashMap::new(),
+            current: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl Scenes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a scene without a timer.
+    pub fn scene(
+        mut self,
+        name: &str,
+        template: impl for<'v> Fn(&'v Vars) -> Activity<'v> + Send + Sync + 'static,
+    ) -> Self {
+        self.scenes.insert(
+            name.to_string(),
+            Scene {
+                template: Box::new(template),
+                timer: None,
+            },
+        );
+        self
+    }
+
+    /// Registers a scene that shows the timer `timer`, applying `rule` when
+    /// the scene is entered.
+    pub fn timed_scene(
+        mut self,
+        name: &str,
+        timer: &str,
+        rule: TimerRule,
+        template: impl for<'v> Fn(&'v Vars) -> Activity<'v> + Send + Sync + 'static,
+    ) -> Self {
+        self.scenes.insert(
+            name.to_string(),
+            Scene {
+                template: Box::new(template),
+                timer: Some((timer.to_string(), rule)),
+            },
+        );
+        self
+    }
+
+    /// The name of the current scene.
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_ref().map(|(name, ..)| name.as_str())
+    }
+
+    /// Switches to the scene `name` and builds its activity from `vars`.
+    ///
+    /// Entering the current scene again only rebuilds the activity; its
+    /// timer keeps running as is.
+    pub fn enter<'v>(
+        &mut self,
+        name: &str,
+        vars: &'v Vars,
+    ) -> io::Result<Activity<'v>> {
+        let Some(timer) =
+            self.scenes.get(name).map(|scene| scene.timer.clone())
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Unknown scene {name}"),
+            ));
+        };
+
+        let start = match &self.current {
+            Some((current, _, start)) if current == name => *start,
+            _ => {
+                self.leave();
+                let start = timer.map(|(timer, rule)| self.start(timer, rule));
+                self.current =
+                    Some((name.to_string(), self.clock.now(), start));
+                start
+            }
+        };
+
+        let activity = (self.scenes[name].template)(vars);
+        Ok(match start {
+            Some(start) => activity.start_time(start),
+            None => activity,
+        })
+    }
+
+    /// Applies `rule` to the timer `name` and returns the start it shows.
+    fn start(&mut self, name: String, rule: TimerRule) -> Timestamp {
+        let now = self.clock.timestamp();
+        let timer = match rule {
+            TimerRule::Reset => {
+                self.timers.insert(name.clone(), Timer::new(now));
+                &self.timers[&name]
+            }
+            TimerRule::Keep | TimerRule::Pause => {
+                self.timers.entry(name).or_insert_with(|| Timer::new(now))
+            }
+        };
+
+        match rule {
+            TimerRule::Reset | TimerRule::Keep => timer.started,
+            TimerRule::Pause => now - timer.elapsed,
+        }
+    }
+
+    /// Adds the time spent in the current scene to its timer.
+    fn leave(&mut self) {
+        let Some((name, entered, _)) = self.current.take() else {
+            return;
+        };
+        if let Some((timer, _)) = self
+            .scenes
+            .get(&name)
+            .and_then(|scene| scene.timer.as_ref())
+        {
+            if let Some(timer) = self.timers.get_mut(timer) {
+                timer.elapsed += self.clock.now().duration_since(entered);
+            }
+        }
+    }
+}
+
+impl Timer {
+    fn new(started: Timestamp) -> Self {
+        Self {
+            started,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::time::SystemTime;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn start(scenes: &mut Scenes, name: &str) -> Option<Timestamp> {
+        let vars = Vars::new();
+        let activity = scenes.enter(name, &vars).unwrap();
+        activity.timestamps.and_then(|timestamps| timestamps.start)
+    }
+
+    #[test]
+    fn timer_rules() {
+        let clock = ManualClock::new(SystemTime::UNIX_EPOCH);
+        let mut scenes = Scenes::new()
+            .scene("menu", |_| Activity::new())
+            .timed_scene("match", "game", TimerRule::Pause, |_| Activity::new())
+            .timed_scene("replay", "game", TimerRule::Keep, |_| Activity::new())
+            .timed_scene("rematch", "game", TimerRule::Reset, |_| {
+                Activity::new()
+            });
+        scenes.clock = Arc::new(clock.clone());
+        let started = clock.timestamp();
+
+        assert_eq!(start(&mut scenes, "match"), Some(started));
+        clock.advance(MINUTE);
+        assert_eq!(start(&mut scenes, "match"), Some(started));
+        assert_eq!(start(&mut scenes, "menu"), None);
+        clock.advance(2 * MINUTE);
+        assert_eq!(scenes.current(), Some("menu"));
+
+        // The three minutes since the start include two spent in the menu.
+        assert_eq!(
+            start(&mut scenes, "match"),
+            Some(clock.timestamp() - MINUTE)
+        );
+        assert_eq!(start(&mut scenes, "replay"), Some(started));
+        assert_eq!(start(&mut scenes, "rematch"), Some(clock.timestamp()));
+    }
+
+    #[test]
+    fn unknown_scene() {
+        let mut scenes = Scenes::new();
+        let err = scenes.enter("lobby", &Vars::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(scenes.current(), None);
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    ffi::{c_int, c_long},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::clock::Clock;
+use crate::rpc::activity::Activity;
+
+const DAY: u32 = 24 * 60 * 60;
+const WEEK: u32 = 7 * DAY;
+
+#[repr(C)]
+struct Tm {
+    tm_sec: c_int,
+    tm_min: c_int,
+    tm_hour: c_int,
+    tm_mday: c_int,
+    tm_mon: c_int,
+    tm_year: c_int,
+    tm_wday: c_int,
+    tm_yday: c_int,
+    tm_isdst: c_int,
+    #[cfg(not(target_os = "windows"))]
+    tm_gmtoff: c_long,
+    #[cfg(not(target_os = "windows"))]
+    tm_zone: *const std::ffi::c_char,
+}
+
+extern "C" {
+    #[cfg(not(target_os = "windows"))]
+    fn localtime_r(time: *const c_long, tm: *mut Tm) -> *mut Tm;
+    #[cfg(target_os = "windows")]
+    fn _localtime64_s(tm: *mut Tm, time: *const i64) -> c_int;
+}
+
+/// A set of days of the week, combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Weekdays(u8);
+
+impl Weekdays {
+    pub const MONDAY: Self = Self(1 << 0);
+    pub const TUESDAY: Self = Self(1 << 1);
+    pub const WEDNESDAY: Self = Self(1 << 2);
+    pub const THURSDAY: Self = Self(1 << 3);
+    pub const FRIDAY: Self = Self(1 << 4);
+    pub const SATURDAY: Self = Self(1 << 5);
+    pub const SUNDAY: Self = Self(1 << 6);
+    pub const WEEKDAYS: Self = Self(0b0011111);
+    pub const WEEKEND: Self = Self(0b1100000);
+    pub const EVERY_DAY: Self = Self(0b1111111);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether the set contains the day `weekday` days after Monday.
+    fn has(&self, weekday: u8) -> bool {
+        self.0 & (1 << (weekday % 7)) != 0
+    }
+}
+
+impl std::ops::BitOr for Weekdays {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A time of the week in the local time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LocalTime {
+    /// Days since Monday, from 0 to 6.
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl LocalTime {
+    pub fn new(weekday: u8, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            weekday,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    /// The current local time, or UTC if the time zone can't be read.
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// `time` in the local time zone, or in UTC if the time zone can't be
+    /// read.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let secs = unix_secs(time);
+        let mut tm: Tm = unsafe { std::mem::zeroed() };
+        #[cfg(not(target_os = "windows"))]
+        let ok = unsafe { !localtime_r(&(secs as c_long), &mut tm).is_null() };
+        #[cfg(target_os = "windows")]
+        let ok = unsafe { _localtime64_s(&mut tm, &(secs as i64)) == 0 };
+
+        if !ok {
+            return Self::utc(time);
+        }
+
+        Self {
+            weekday: ((tm.tm_wday + 6) % 7) as u8,
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+            // Leap seconds are folded into the last second of the minute.
+            second: tm.tm_sec.min(59) as u8,
+        }
+    }
+
+    /// `time` in UTC.
+    pub fn utc(time: SystemTime) -> Self {
+        let secs = unix_secs(time);
+        // 1970-01-01 was a Thursday.
+        let days = secs / DAY as u64;
+        Self::from_secs(
+            ((days + 3) % 7) as u32 * DAY + (secs % DAY as u64) as u32,
+        )
+    }
+
+    /// Seconds since the start of Monday.
+    fn as_secs(&self) -> u32 {
+        self.weekday as u32 % 7 * DAY
+            + self.hour as u32 * 3600
+            + self.minute as u32 * 60
+            + self.second as u32
+    }
+
+    fn from_secs(secs: u32) -> Self {
+        let secs = secs % WEEK;
+        Self {
+            weekday: (secs / DAY) as u8,
+            hour: (secs % DAY / 3600) as u8,
+            minute: (secs % 3600 / 60) as u8,
+            second: (secs % 60) as u8,
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A window of time on some days of the week, and what to show during it.
+#[derive(Clone)]
+pub(crate) struct Rule<T> {
+    days: Weekdays,
+    /// Seconds since midnight.
+    from: u32,
+    to: u32,
+    pub(crate) action: T,
+}
+
+impl<T> Rule<T> {
+    pub(crate) fn map<U>(&self, f: impl FnOnce(&T) -> U) -> Rule<U> {
+        Rule {
+            days: self.days,
+            from: self.from,
+            to: self.to,
+            action: f(&self.action),
+        }
+    }
+
+    /// Whether the window contains `time`. A window that ends at or before
+    /// it starts runs past midnight into the next day, for a full day if
+    /// both are the same.
+    fn matches(&self, time: LocalTime) -> bool {
+        let secs = time.as_secs() % DAY;
+        if self.from < self.to {
+            self.days.has(time.weekday) && (self.from..self.to).contains(&secs)
+        } else {
+            self.days.has(time.weekday) && secs >= self.from
+                || self.days.has(time.weekday + 6) && secs < self.to
+        }
+    }
+
+    /// The seconds since the start of Monday at which the window starts or
+    /// ends.
+    fn boundaries(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..7)
+            .filter(|&day| self.days.has(day))
+            .flat_map(move |day| {
+                let start = day as u32 * DAY;
+                let end = if self.from < self.to {
+                    start
+                } else {
+                    start + DAY
+                };
+                [start + self.from, (end + self.to) % WEEK]
+            })
+    }
+}
+
+/// Rules that pick what presence to show by the day of the week and time of
+/// day, in local time.
+///
+/// Each rule covers a window on some days, from its start up to, but not
+/// including, its end. A window ending at or before its start runs past
+/// midnight, so `22:00` to `00:00` covers the rest of the day, and one
+/// ending at its start lasts 24 hours, so `00:00` to `00:00` covers the
+/// whole day. Where rules overlap, the one added last wins, so overrides
+/// such as a lunch break go after the rules they override.
+///
+/// Hand a schedule to
+/// [`RichClientHandle::set_schedule`](crate::RichClientHandle::set_schedule)
+/// to have it applied in the background, by the client's
+/// [`Clock`].
+#[derive(Clone, Default)]
+pub struct Schedule<'a> {
+    pub(crate) rules: Vec<Rule<Option<Activity<'a>>>>,
+}
+
+impl<'a> Schedule<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shows `activity` on `days` from `from` until `to`, given as hours
+    /// and minutes.
+    pub fn show(
+        self,
+        days: Weekdays,
+        from: (u8, u8),
+        to: (u8, u8),
+        activity: Activity<'a>,
+    ) -> Self {
+        self.rule(days, from, to, Some(activity))
+    }
+
+    /// Clears the presence on `days` from `from` until `to`, given as hours
+    /// and minutes.
+    pub fn clear(self, days: Weekdays, from: (u8, u8), to: (u8, u8)) -> Self {
+        self.rule(days, from, to, None)
+    }
+
+    fn rule(
+        mut self,
+        days: Weekdays,
+        from: (u8, u8),
+        to: (u8, u8),
+        action: Option<Activity<'a>>,
+    ) -> Self {
+        let secs = |(hour, minute): (u8, u8)| {
+            (hour as u32 * 3600 + minute as u32 * 60) % DAY
+        };
+        self.rules.push(Rule {
+            days,
+            from: secs(from),
+            to: secs(to),
+            action,
+        });
+        self
+    }
+
+    /// What the schedule shows at `time`: `None` if no rule covers it, or
+    /// the activity of the rule that wins, `None` for a cleared presence.
+    pub fn active_at(&self, time: LocalTime) -> Option<Option<&Activity<'a>>> {
+        active(&self.rules, time).map(Option::as_ref)
+    }
+
+    /// What the schedule shows now, by `clock`. See
+    /// [`Schedule::active_at`].
+    pub fn active(&self, clock: &dyn Clock) -> Option<Option<&Activity<'a>>> {
+        self.active_at(clock.local_time())
+    }
+}
+
+pub(crate) fn active<T>(rules: &[Rule<T>], time: LocalTime) -> Option<&T> {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(time))
+        .map(|rule| &rule.action)
+}
+
+/// How long after `time` a window of `rules` next starts or ends.
+pub(crate) fn next_change<T>(
+    rules: &[Rule<T>],
+    time: LocalTime,
+) -> Option<Duration> {
+    let now = time.as_secs();
+    rules
+        .iter()
+        .flat_map(Rule::boundaries)
+        .map(|boundary| match (boundary + WEEK - now) % WEEK {
+            0 => WEEK,
+            secs => secs,
+        })
+        .min()
+        .map(|secs| Duration::from_secs(secs as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONDAY: u8 = 0;
+    const FRIDAY: u8 = 4;
+    const SATURDAY: u8 = 5;
+    const SUNDAY: u8 = 6;
+
+    fn at(weekday: u8, hour: u8, minute: u8) -> LocalTime {
+        LocalTime::new(weekday, hour, minute, 0)
+    }
+
+    fn details<'s>(schedule: &'s Schedule, time: LocalTime) -> Option<&'s str> {
+        schedule
+            .active_at(time)?
+            .and_then(|activity| activity.details)
+    }
+
+    #[test]
+    fn utc_weekday() {
+        assert_eq!(LocalTime::utc(UNIX_EPOCH), at(3, 0, 0));
+        // Monday, 2024-01-01 00:00 UTC.
+        let monday = UNIX_EPOCH + Duration::from_secs(1704067200);
+        assert_eq!(LocalTime::utc(monday), at(MONDAY, 0, 0));
+        assert_eq!(
+            LocalTime::utc(monday - Duration::from_secs(1)),
+            LocalTime::new(SUNDAY, 23, 59, 59)
+        );
+    }
+
+    #[test]
+    fn window_across_midnight() {
+        let schedule = Schedule::new().show(
+            Weekdays::FRIDAY,
+            (22, 0),
+            (2, 0),
+            Activity::new().details("late"),
+        );
+
+        assert_eq!(details(&schedule, at(FRIDAY, 21, 59)), None);
+        assert_eq!(details(&schedule, at(FRIDAY, 22, 0)), Some("late"));
+        assert_eq!(details(&schedule, at(SATURDAY, 1, 59)), Some("late"));
+        assert_eq!(details(&schedule, at(SATURDAY, 2, 0)), None);
+        // The window starts on Friday only, so it doesn't run into Friday.
+        assert_eq!(details(&schedule, at(FRIDAY, 1, 0)), None);
+    }
+
+    #[test]
+    fn window_ending_at_its_start_lasts_a_day() {
+        let schedule = Schedule::new().show(
+            Weekdays::MONDAY,
+            (0, 0),
+            (0, 0),
+            Activity::new().details("all day"),
+        );
+
+        assert_eq!(details(&schedule, at(SUNDAY, 23, 59)), None);
+        assert_eq!(details(&schedule, at(MONDAY, 0, 0)), Some("all day"));
+        assert_eq!(
+            details(&schedule, LocalTime::new(MONDAY, 23, 59, 59)),
+            Some("all day")
+        );
+        assert_eq!(details(&schedule, at(1, 0, 0)), None);
+        assert_eq!(
+            next_change(&schedule.rules, at(MONDAY, 12, 0)),
+            Some(Duration::from_secs(12 * 3600))
+        );
+    }
+
+    #[test]
+    fn sunday_wraps_to_monday() {
+        let schedule = Schedule::new().clear(Weekdays::SUNDAY, (22, 0), (6, 0));
+
+        assert_eq!(schedule.active_at(at(SUNDAY, 23, 0)), Some(None));
+        assert_eq!(schedule.active_at(at(MONDAY, 5, 59)), Some(None));
+        assert_eq!(schedule.active_at(at(MONDAY, 6, 0)), None);
+        assert_eq!(schedule.active_at(at(SATURDAY, 23, 0)), None);
+        assert_eq!(
+            next_change(&schedule.rules, at(SUNDAY, 23, 0)),
+            Some(Duration::from_secs(7 * 3600))
+        );
+    }
+
+    #[test]
+    fn later_rules_win() {
+        let schedule = Schedule::new()
+            .show(
+                Weekdays::WEEKDAYS,
+                (9, 0),
+                (17, 0),
+                Activity::new().details("work"),
+            )
+            .show(
+                Weekdays::EVERY_DAY,
+                (12, 0),
+                (13, 0),
+                Activity::new().details("lunch"),
+            );
+
+        assert_eq!(details(&schedule, at(MONDAY, 11, 59)), Some("work"));
+        assert_eq!(details(&schedule, at(MONDAY, 12, 30)), Some("lunch"));
+        assert_eq!(details(&schedule, at(SATURDAY, 12, 30)), Some("lunch"));
+        assert_eq!(details(&schedule, at(SATURDAY, 11, 0)), None);
+    }
+
+    #[test]
+    fn next_change_at_boundary() {
+        let schedule = Schedule::new().show(
+            Weekdays::WEEKDAYS,
+            (9, 0),
+            (17, 0),
+            Activity::new().details("work"),
+        );
+        let next = |time| next_change(&schedule.rules, time);
+
+        assert_eq!(
+            next(LocalTime::new(MONDAY, 8, 59, 59)),
+            Some(Duration::from_secs(1))
+        );
+        // A boundary that was just reached is not reported again.
+        assert_eq!(next(at(MONDAY, 9, 0)), Some(Duration::from_secs(8 * 3600)));
+        assert_eq!(
+            next(at(FRIDAY, 17, 0)),
+            Some(Duration::from_secs((2 * 24 + 16) * 3600))
+        );
+
+        let once = Schedule::new().clear(Weekdays::MONDAY, (9, 0), (9, 0));
+        assert_eq!(
+            next_change(&once.rules, at(MONDAY, 9, 0)),
+            Some(Duration::from_secs(24 * 3600))
+        );
+        assert_eq!(
+            next_change(&once.rules, at(1, 9, 0)),
+            Some(Duration::from_secs(6 * 24 * 3600))
+        );
+        assert_eq!(next_change::<()>(&[], at(MONDAY, 9, 0)), None);
+    }
+}
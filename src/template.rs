@@ -0,0 +1,468 @@
+use std::{fmt, fs, io, path::Path};
+
+use crate::json::Value;
+use crate::rpc::activity::{Activity, ActivityType};
+use crate::scene::Vars;
+
+/// A text field of an activity that a template can fill in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Details,
+    DetailsUrl,
+    State,
+    StateUrl,
+    LargeImage,
+    LargeText,
+    LargeUrl,
+    SmallImage,
+    SmallText,
+    SmallUrl,
+    PartyId,
+}
+
+const FIELDS: &[Field] = &[
+    Field::Name,
+    Field::Details,
+    Field::DetailsUrl,
+    Field::State,
+    Field::StateUrl,
+    Field::LargeImage,
+    Field::LargeText,
+    Field::LargeUrl,
+    Field::SmallImage,
+    Field::SmallText,
+    Field::SmallUrl,
+    Field::PartyId,
+];
+
+impl Field {
+    /// The key of the field in a template config.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Field::Name => "name",
+            Field::Details => "details",
+            Field::DetailsUrl => "details_url",
+            Field::State => "state",
+            Field::StateUrl => "state_url",
+            Field::LargeImage => "large_image",
+            Field::LargeText => "large_text",
+            Field::LargeUrl => "large_url",
+            Field::SmallImage => "small_image",
+            Field::SmallText => "small_text",
+            Field::SmallUrl => "small_url",
+            Field::PartyId => "party_id",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        FIELDS.iter().copied().find(|field| field.key() == key)
+    }
+}
+
+/// Error returned when a template is malformed or can't be rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// The text of `field` has a malformed placeholder at `offset`.
+    Syntax {
+        field: Field,
+        offset: usize,
+        message: &'static str,
+    },
+    /// The required `field` uses `variable`, which was not given.
+    MissingVariable { field: Field, variable: String },
+    /// The template config is not valid.
+    Config(String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::Syntax {
+                field,
+                offset,
+                message,
+            } => write!(f, "{}: {message} at offset {offset}", field.key()),
+            TemplateError::MissingVariable { field, variable } => {
+                write!(f, "{}: missing variable `{variable}`", field.key())
+            }
+            TemplateError::Config(message) => {
+                write!(f, "invalid template config: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<TemplateError> for io::Error {
+    fn from(err: TemplateError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// A piece of a field's text.
+enum Segment<'t> {
+    Literal(&'t str),
+    Variable(&'t str),
+}
+
+/// Splits `text` into literals and `{variable}` placeholders. Braces are
+/// escaped by doubling them.
+fn parse(field: Field, text: &str) -> Result<Vec<Segment<'_>>, TemplateError> {
+    let error = |offset, message| TemplateError::Syntax {
+        field,
+        offset,
+        message,
+    };
+
+    let mut segments = Vec::new();
+    let mut literal = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                segments.push(Segment::Literal(&text[literal..=offset]));
+                chars.next();
+                literal = offset + 2;
+            }
+            '{' => {
+                let Some(len) = text[offset + 1..].find('}') else {
+                    return Err(error(offset, "unclosed placeholder"));
+                };
+                let name = &text[offset + 1..offset + 1 + len];
+                if name.is_empty() || name.contains('{') {
+                    return Err(error(offset, "invalid placeholder"));
+                }
+                segments.push(Segment::Literal(&text[literal..offset]));
+                segments.push(Segment::Variable(name));
+                literal = offset + len + 2;
+                while chars.peek().is_some_and(|&(at, _)| at < literal) {
+                    chars.next();
+                }
+            }
+            '}' => return Err(error(offset, "unmatched `}`")),
+            _ => {}
+        }
+    }
+    segments.push(Segment::Literal(&text[literal..]));
+
+    Ok(segments)
+}
+
+/// An activity whose text fields contain `{variable}` placeholders, rendered
+/// against a set of variables.
+///
+/// Required fields fail to render if a variable they use is missing, while
+/// optional fields are left out of the activity instead. Use `{{` and `}}`
+/// for literal braces.
+///
+/// ```json
+/// {
+///     "details": "Editing {file}",
+///     "state": "{lines} lines",
+///     "large_image": "{lang}",
+///     "optional": ["state"]
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ActivityTemplate {
+    ty: ActivityType,
+    /// Each field's text and whether it is optional.
+    fields: Vec<(Field, String, bool)>,
+}
+
+impl ActivityTemplate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ty(mut self, ty: ActivityType) -> Self {
+        self.ty = ty;
+        self
+    }
+
+    /// Sets the text of a required field.
+    pub fn field(self, field: Field, text: &str) -> Self {
+        self.set(field, text, false)
+    }
+
+    /// Sets the text of a field that is left out if a variable it uses is
+    /// missing.
+    pub fn optional(self, field: Field, text: &str) -> Self {
+        self.set(field, text, true)
+    }
+
+    fn set(mut self, field: Field, text: &str, optional: bool) -> Self {
+        self.fields.retain(|(f, ..)| *f != field);
+        self.fields.push((field, text.to_string(), optional));
+        self
+    }
+
+    /// Parses a template from a JSON config, with a key per field, an
+    /// optional activity `type` and an `optional` array of field keys.
+    pub fn from_json(json: &str) -> Result<Self, TemplateError> {
+        let config: Value = json
+            .parse()
+            .map_err(|e| TemplateError::Config(format!("{e}")))?;
+        let Some(entries) = config.as_object() else {
+            return Err(TemplateError::Config("expected an object".into()));
+        };
+
+        let optional = match config.get("optional") {
+            None => Vec::new(),
+            Some(optional) => optional
+                .as_array()
+                .and_then(|keys| {
+                    keys.iter().map(Value::as_str).collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    TemplateError::Config(
+                        "`optional` must be an array of field names".into(),
+                    )
+                })?,
+        };
+
+        let mut template = Self::new();
+        for (key, value) in entries {
+            match key.as_str() {
+                "optional" => continue,
+                "type" => {
+                    template.ty =
+                        match value.as_str() {
+                            Some("playing") => ActivityType::Playing,
+                            Some("listening") => ActivityType::Listening,
+                            Some("watching") => ActivityType::Watching,
+                            Some("competing") => ActivityType::Competing,
+                            _ => return Err(TemplateError::Config(
+                                "`type` must be one of playing, listening, \
+                                 watching or competing"
+                                    .into(),
+                            )),
+                        };
+                    continue;
+                }
+                _ => {}
+            }
+
+            let Some(field) = Field::from_key(key) else {
+                return Err(TemplateError::Config(format!(
+                    "unknown field `{key}`"
+                )));
+            };
+            let Some(text) = value.as_str() else {
+                return Err(TemplateError::Config(format!(
+                    "`{key}` must be a string"
+                )));
+            };
+            parse(field, text)?;
+            template =
+                template.set(field, text, optional.contains(&key.as_str()));
+        }
+
+        if let Some(key) = optional
+            .iter()
+            .find(|&&key| !template.fields.iter().any(|(f, ..)| f.key() == key))
+        {
+            return Err(TemplateError::Config(format!(
+                "`optional` names `{key}`, which is not set"
+            )));
+        }
+
+        Ok(template)
+    }
+
+    /// Reads a template from the JSON config at `path`. See
+    /// [`ActivityTemplate::from_json`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_json(&fs::read_to_string(path)?)?)
+    }
+
+    /// Fills in the fields with `vars`.
+    pub fn render(&self, vars: &Vars) -> Result<Rendered, TemplateError> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        'fields: for (field, text, optional) in &self.fields {
+            let mut rendered = String::with_capacity(text.len());
+            for segment in parse(*field, text)? {
+                match segment {
+                    Segment::Literal(literal) => rendered.push_str(literal),
+                    Segment::Variable(name) => match vars.get(name) {
+                        Some(value) => rendered.push_str(value),
+                        None if *optional => continue 'fields,
+                        None => {
+                            return Err(TemplateError::MissingVariable {
+                                field: *field,
+                                variable: name.to_string(),
+                            })
+                        }
+                    },
+                }
+            }
+            fields.push((*field, rendered));
+        }
+
+        Ok(Rendered {
+            ty: self.ty.clone(),
+            fields,
+        })
+    }
+}
+
+/// The fields of an [`ActivityTemplate`] filled in, which the activity
+/// borrows from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rendered {
+    ty: ActivityType,
+    fields: Vec<(Field, String)>,
+}
+
+impl Rendered {
+    pub fn activity(&self) -> Activity<'_> {
+        self.fields.iter().fold(
+            Activity::new().ty(self.ty.clone()),
+            |activity, (field, text)| match field {
+                Field::Name => activity.name(text),
+                Field::Details => activity.details(text),
+                Field::DetailsUrl => activity.details_url(text),
+                Field::State => activity.state(text),
+                Field::StateUrl => activity.state_url(text),
+                Field::LargeImage => activity.large_image(text),
+                Field::LargeText => activity.large_text(text),
+                Field::LargeUrl => activity.large_url(text),
+                Field::SmallImage => activity.small_image(text),
+                Field::SmallText => activity.small_text(text),
+                Field::SmallUrl => activity.small_url(text),
+                Field::PartyId => activity.party_id(text),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vars {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn render(
+        template: &ActivityTemplate,
+        vars: &Vars,
+    ) -> Vec<(Field, String)> {
+        template.render(vars).unwrap().fields
+    }
+
+    fn syntax_error(text: &str) -> (usize, &'static str) {
+        match parse(Field::Details, text) {
+            Err(TemplateError::Syntax {
+                offset, message, ..
+            }) => (offset, message),
+            Err(e) => panic!("expected a syntax error, got {e}"),
+            Ok(_) => panic!("expected a syntax error for {text:?}"),
+        }
+    }
+
+    fn config_error(json: &str) -> String {
+        match ActivityTemplate::from_json(json) {
+            Err(TemplateError::Config(message)) => message,
+            Err(e) => panic!("expected a config error, got {e}"),
+            Ok(_) => panic!("expected a config error for {json}"),
+        }
+    }
+
+    #[test]
+    fn fills_in_placeholders() {
+        let template = ActivityTemplate::new()
+            .field(Field::Details, "Editing {file} in {project}")
+            .field(Field::State, "{lines}");
+        let vars =
+            vars(&[("file", "main.rs"), ("project", "x"), ("lines", "3")]);
+        assert_eq!(
+            render(&template, &vars),
+            [
+                (Field::Details, "Editing main.rs in x".to_string()),
+                (Field::State, "3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let template =
+            ActivityTemplate::new().field(Field::Details, "{{{name}}} }}{{");
+        assert_eq!(
+            render(&template, &vars(&[("name", "x")])),
+            [(Field::Details, "{x} }{".to_string())]
+        );
+    }
+
+    #[test]
+    fn malformed_placeholders() {
+        assert_eq!(syntax_error("a {b"), (2, "unclosed placeholder"));
+        assert_eq!(syntax_error("a {}"), (2, "invalid placeholder"));
+        assert_eq!(syntax_error("{a{b}"), (0, "invalid placeholder"));
+        assert_eq!(syntax_error("a } b"), (2, "unmatched `}`"));
+        assert_eq!(syntax_error("{a}}"), (3, "unmatched `}`"));
+    }
+
+    #[test]
+    fn optional_fields_are_left_out() {
+        let template = ActivityTemplate::new()
+            .field(Field::Details, "{file}")
+            .optional(Field::State, "{lines} lines");
+        assert_eq!(
+            render(&template, &vars(&[("file", "a")])),
+            [(Field::Details, "a".to_string())]
+        );
+
+        assert_eq!(
+            template.render(&vars(&[("lines", "3")])),
+            Err(TemplateError::MissingVariable {
+                field: Field::Details,
+                variable: "file".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn reads_config() {
+        let template = ActivityTemplate::from_json(
+            r#"{"type": "listening", "details": "{song}", "state": "{artist}",
+                "optional": ["state"]}"#,
+        )
+        .unwrap();
+        let rendered = template.render(&vars(&[("song", "s")])).unwrap();
+        assert_eq!(rendered.ty, ActivityType::Listening);
+        assert_eq!(rendered.fields, [(Field::Details, "s".to_string())]);
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert_eq!(
+            config_error(r#"{"detail": "x"}"#),
+            "unknown field `detail`"
+        );
+        assert!(config_error(r#"{"type": "streaming"}"#).starts_with("`type`"));
+        assert_eq!(
+            config_error(r#"{"details": "x", "optional": ["state"]}"#),
+            "`optional` names `state`, which is not set"
+        );
+        assert_eq!(
+            config_error(r#"{"optional": "state"}"#),
+            "`optional` must be an array of field names"
+        );
+        assert_eq!(
+            config_error(r#"{"details": 1}"#),
+            "`details` must be a string"
+        );
+        assert_eq!(config_error("[]"), "expected an object");
+        assert!(matches!(
+            ActivityTemplate::from_json(r#"{"details": "{x"}"#),
+            Err(TemplateError::Syntax { .. })
+        ));
+    }
+}